@@ -0,0 +1,33 @@
+// Times a 10,000-simulation connect4 search from the empty board with the Rc<RefCell<Node>> tree and with the
+// index-based Arena
+//
+//     cargo run --release --example bench_arena
+
+use mcts::arena::Arena;
+use mcts::connect4::State;
+use mcts::mcts::Node;
+use std::time::Instant;
+
+const SIMULATIONS: i32 = 10_000;
+const RUNS: u32 = 5;
+
+// Mean seconds per call of `search` over RUNS calls
+fn time(mut search: impl FnMut()) -> f64 {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        search();
+    }
+    start.elapsed().as_secs_f64() / RUNS as f64
+}
+
+fn main() {
+    let node = time(|| {
+        Node::best_action(&Node::new(State::new(), None, None), SIMULATIONS);
+    });
+    let arena = time(|| {
+        Arena::new(State::new()).best_action(SIMULATIONS);
+    });
+    println!("{} simulations, mean of {} searches:", SIMULATIONS, RUNS);
+    println!("  Node:  {:.3}s", node);
+    println!("  Arena: {:.3}s ({:.0}% of Node)", arena, 100. * arena / node);
+}
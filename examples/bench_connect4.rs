@@ -0,0 +1,68 @@
+// Times a 10,000-simulation connect4 search from the empty board with the winner cached on each state, against
+// the same search rescanning the whole board whenever it asks whether the game is over
+//
+//     cargo run --release --example bench_connect4 --features testing
+
+use mcts::connect4;
+use mcts::mcts::{GameState, GameStatus, Node};
+use std::time::Instant;
+
+const SIMULATIONS: i32 = 10_000;
+const RUNS: u32 = 5;
+
+// connect4 with status() and is_terminal() worked out from the board on every call
+#[derive(Clone)]
+struct Rescanned(connect4::State);
+
+impl GameState for Rescanned {
+    type Action = usize;
+
+    fn status(&self) -> GameStatus {
+        self.0.scanned_status()
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.0.get_player_turn()
+    }
+
+    fn get_legal_actions(&self) -> Vec<usize> {
+        self.0.get_legal_actions()
+    }
+
+    fn get_policy(&self, actions: &[usize]) -> Vec<f64> {
+        self.0.get_policy(actions)
+    }
+
+    fn evaluate(&self) -> f64 {
+        self.0.evaluate()
+    }
+
+    fn get_next_state(&self, action: usize) -> Rescanned {
+        Rescanned(self.0.get_next_state(action))
+    }
+
+    fn hash_key(&self) -> u64 {
+        self.0.hash_key()
+    }
+}
+
+// Mean seconds per call of `search` over RUNS calls
+fn time(mut search: impl FnMut()) -> f64 {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        search();
+    }
+    start.elapsed().as_secs_f64() / RUNS as f64
+}
+
+fn main() {
+    let rescanned = time(|| {
+        Node::best_action(&Node::new(Rescanned(connect4::State::new()), None, None), SIMULATIONS);
+    });
+    let cached = time(|| {
+        Node::best_action(&Node::new(connect4::State::new(), None, None), SIMULATIONS);
+    });
+    println!("{} simulations, mean of {} searches:", SIMULATIONS, RUNS);
+    println!("  Rescanned: {:.3}s", rescanned);
+    println!("  Cached:    {:.3}s ({:.0}% of rescanned)", cached, 100. * cached / rescanned);
+}
@@ -0,0 +1,64 @@
+// Counts the get_policy() calls the tree makes in a 10,000-simulation connect4 search, against the number of
+// nodes it creates, which is how many calls there were when Node::new() computed every node's priors up front
+//
+//     cargo run --release --example bench_policy_calls
+
+use mcts::connect4;
+use mcts::mcts::{GameState, GameStatus, Node};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const SIMULATIONS: i32 = 10_000;
+
+static POLICY_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+// connect4 with a counter on get_policy(). Rollouts sample from the same weights through get_rollout_policy(),
+// which isn't counted, so the count is the tree's alone.
+#[derive(Clone)]
+struct Counted(connect4::State);
+
+impl GameState for Counted {
+    type Action = usize;
+
+    fn status(&self) -> GameStatus {
+        self.0.status()
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.0.is_terminal()
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.0.get_player_turn()
+    }
+
+    fn get_legal_actions(&self) -> Vec<usize> {
+        self.0.get_legal_actions()
+    }
+
+    fn get_policy(&self, actions: &[usize]) -> Vec<f64> {
+        POLICY_CALLS.fetch_add(1, Ordering::Relaxed);
+        self.0.get_policy(actions)
+    }
+
+    fn get_rollout_policy(&self, actions: &[usize]) -> Vec<f64> {
+        self.0.get_policy(actions)
+    }
+
+    fn get_next_state(&self, action: usize) -> Counted {
+        Counted(self.0.get_next_state(action))
+    }
+
+    fn hash_key(&self) -> u64 {
+        self.0.hash_key()
+    }
+}
+
+fn main() {
+    let root = Node::new(Counted(connect4::State::new()), None, None);
+    Node::best_action(&root, SIMULATIONS);
+    let calls = POLICY_CALLS.load(Ordering::Relaxed);
+    let nodes = Node::tree_size(&root);
+    println!("{} simulations:", SIMULATIONS);
+    println!("  get_policy calls: {}", calls);
+    println!("  nodes created:    {} ({:.1}x the calls)", nodes, nodes as f64 / calls as f64);
+}
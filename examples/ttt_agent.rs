@@ -0,0 +1,28 @@
+// Plays a game of tic-tac-toe between an Agent and a player who always takes the first free cell
+//
+//     cargo run --example ttt_agent
+
+use mcts::agent::Agent;
+use mcts::mcts::{GameState, GameStatus, SearchConfig};
+use mcts::ttt::State;
+
+fn main() {
+    let mut agent = Agent::new(State::new(), SearchConfig::default());
+    while !agent.state().is_terminal() {
+        // The agent plays X, and O replies with the first legal move
+        let action = if agent.state().get_player_turn() == 1 {
+            agent.think().expect("the game isn't over")
+        } else {
+            agent.state().get_legal_actions()[0]
+        };
+        let player = if agent.state().get_player_turn() == 1 { 'X' } else { 'O' };
+        let stats = agent.apply(action);
+        println!("{} plays {:?}, keeping {} of {} visits", player, action, stats.reused_visits, stats.reused_visits + stats.discarded_visits);
+    }
+
+    match agent.state().status() {
+        GameStatus::Win(1) => println!("X wins!"),
+        GameStatus::Win(_) => println!("O wins!"),
+        _ => println!("It's a draw!"),
+    }
+}
@@ -0,0 +1,49 @@
+// A connect4 opponent for a web page, built without the terminal UI or the OS-seeded RNG:
+//
+//     cargo build --release --example wasm_connect4 --target wasm32-unknown-unknown --no-default-features
+//
+// The page supplies the seed, so the search never needs an entropy source or a clock.
+
+use mcts::connect4::State;
+use mcts::mcts::{GameState, GameStatus, Node, SearchConfig};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::cell::RefCell;
+
+thread_local! {
+    static GAME: RefCell<State> = RefCell::new(State::new());
+}
+
+#[no_mangle]
+pub extern "C" fn reset() {
+    GAME.with(|game| *game.borrow_mut() = State::new());
+}
+
+// Drops a piece into `col`, returning 0 while the game goes on, 1 or -1 for a win by X or O, 2 for a draw
+// and -2 if the move isn't legal
+#[no_mangle]
+pub extern "C" fn play(col: u32) -> i32 {
+    GAME.with(|game| {
+        let mut game = game.borrow_mut();
+        if !game.get_legal_actions().contains(&(col as usize)) {
+            return -2;
+        }
+        *game = game.get_next_state(col as usize);
+        match game.status() {
+            GameStatus::InProgress => 0,
+            GameStatus::Win(player) => player,
+            GameStatus::Draw => 2,
+        }
+    })
+}
+
+// The column the AI would play, or -1 if the game is over
+#[no_mangle]
+pub extern "C" fn think(seed: u32, n_simulations: i32) -> i32 {
+    GAME.with(|game| {
+        let root = Node::new(game.borrow().clone(), None, None);
+        let config = SearchConfig { n_simulations, ..SearchConfig::default() };
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        Node::best_action_with_rng(&root, &config, &mut rng).map_or(-1, |col| col as i32)
+    })
+}
@@ -0,0 +1,39 @@
+use crate::mcts::{Node, GameState, SearchConfig, AdvanceStats};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+
+// A player that keeps its search tree between moves, for driving games from outside the crate
+pub struct Agent<State: GameState> {
+    root: Rc<RefCell<Node<State>>>,
+    state: State,
+    config: SearchConfig,
+}
+
+impl<State: GameState + Clone> Agent<State> {
+    pub fn new(state: State, config: SearchConfig) -> Self {
+        Agent {
+            root: Node::new(state.clone(), None, None),
+            state,
+            config,
+        }
+    }
+
+    // Searches from the current state and returns the move to play, or None if the game is over.
+    // The move isn't applied, so call apply() with it (or with whatever move is actually played).
+    pub fn think(&mut self) -> Option<State::Action> {
+        Node::best_action_with_config(&self.root, &self.config)
+    }
+
+    // Plays a move by either side, keeping the part of the tree below it and reporting how much that was
+    pub fn apply(&mut self, action: State::Action) -> AdvanceStats {
+        let (root, stats) = Node::advance_with_stats(&self.root, action);
+        self.root = root;
+        self.state = self.root.borrow().state.clone();
+        stats
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+}
@@ -0,0 +1,210 @@
+use crate::mcts::{ChildStat, ConfigError, GameState, SearchConfig, check_supported, compare_scores, default_rng, final_action, mean_value, prune_losing_moves, rollout, selection_score, shortcut_action};
+use std::collections::HashMap;
+
+// Index-based alternative to the Rc<RefCell<Node>> tree: all nodes live in one Vec, with
+// parents and children referenced by position, so the hot loop has no refcounts or borrow checks.
+// Chance nodes aren't supported here, so games with GameState::is_chance_node() need the Node search. The
+// final move is chosen as in Node, but best_action_with_config() rejects the options that change the shape of the
+// tree, which the arena doesn't do (see check_supported()).
+
+const ROOT: usize = 0;
+
+struct ArenaNode<State: GameState> {
+    state: State,
+    parent: Option<usize>,
+    parent_action: Option<State::Action>,
+    children: Vec<usize>,
+    total_values: Vec<f64>,
+    visit_count: i32,
+    action_probs: HashMap<State::Action, f64>,
+}
+
+pub struct Arena<State: GameState> {
+    nodes: Vec<ArenaNode<State>>,
+}
+
+impl<State: GameState + Clone> Arena<State> {
+    pub fn new(state: State) -> Self {
+        let mut arena = Arena { nodes: Vec::new() };
+        arena.add_node(state, None, None);
+        arena
+    }
+
+    fn add_node(&mut self, state: State, parent: Option<usize>, parent_action: Option<State::Action>) -> usize {
+        self.nodes.push(ArenaNode {
+            total_values: vec![0.; state.num_players()],
+            state,
+            parent,
+            parent_action,
+            children: Vec::new(),
+            visit_count: 0,
+            action_probs: HashMap::new(),
+        });
+        self.nodes.len() - 1
+    }
+
+    // Step 1: Select and expand
+
+    fn select_node(&mut self, config: &SearchConfig) -> usize {
+        let mut current = ROOT;
+
+        while !self.nodes[current].state.is_terminal() {
+            if self.nodes[current].children.is_empty() {
+                self.expand(current, config);
+                if self.nodes[current].children.is_empty() {
+                    break;  // No legal moves, so treat it as a terminal leaf
+                }
+                return self.best_child(current, config);
+            } else {
+                current = self.best_child(current, config);
+            }
+        }
+
+        current
+    }
+
+    fn best_child(&self, index: usize, config: &SearchConfig) -> usize {
+        let node = &self.nodes[index];
+        let player = node.state.get_player_index();
+        let score = |child: usize| {
+            let child = &self.nodes[child];
+            let prior = node.action_probs.get(&child.parent_action.unwrap()).copied().unwrap_or(0.0);
+            let value = mean_value(&child.total_values, player, child.visit_count);
+            selection_score(config, node.visit_count, child.visit_count, value, prior)
+        };
+        let prior = |child: usize| node.action_probs.get(&self.nodes[child].parent_action.unwrap()).copied().unwrap_or(0.0);
+        // Ties go to the higher prior, then to the child expanded first, as in Node::best_child()
+        node.children.iter().copied().rev().max_by(|&a, &b| {
+            compare_scores(score(a), score(b)).then_with(|| compare_scores(prior(a), prior(b)))
+        }).expect("Unable to find best child node")
+    }
+
+    fn expand(&mut self, index: usize, config: &SearchConfig) {
+        assert!(!self.nodes[index].state.is_terminal(), "Attempted to expand a terminal node.");
+        assert!(self.nodes[index].children.is_empty(), "Attempted to re-expand a node.");
+
+        // Priors are only needed once a node has children to choose between, so they're computed here
+        let state = self.nodes[index].state.clone();
+        let actions = state.get_legal_actions();
+        let weights = state.get_policy(&actions);
+        self.nodes[index].action_probs = actions.iter().copied().zip(weights).collect();
+        let mut children: Vec<(State::Action, State)> = actions.iter()
+            .filter(|action| self.nodes[index].action_probs.get(action).is_some_and(|&prob| prob > 1e-6 || prob.is_nan()))
+            .map(|&action| (action, state.get_next_state(action)))
+            .collect();
+        if config.prune_losing_moves {
+            prune_losing_moves(state.get_player_turn(), &mut children);
+        }
+        for (action, child_state) in children {
+            let child = self.add_node(child_state, Some(index), Some(action));
+            self.nodes[index].children.push(child);
+        }
+    }
+
+    // Step 2: Backpropagate the results of a rollout
+
+    fn backpropagate(&mut self, index: usize, results: &[f64]) {
+        let mut current = Some(index);
+        while let Some(index) = current {
+            let node = &mut self.nodes[index];
+            node.visit_count += 1;
+            node.total_values.iter_mut().zip(results).for_each(|(total, result)| *total += result);
+            current = node.parent;
+        }
+    }
+
+    // Tree search
+
+    pub fn best_action(&mut self, n_simulations: i32) -> Option<State::Action> {
+        let config = SearchConfig { n_simulations, ..SearchConfig::default() };
+        self.best_action_with_config(&config).expect("the default config is supported")
+    }
+
+    pub fn best_action_with_config(&mut self, config: &SearchConfig) -> Result<Option<State::Action>, ConfigError> {
+        check_supported(config, "the arena")?;
+        if self.nodes[ROOT].state.is_terminal() {
+            return Ok(None);
+        }
+        if let Some(action) = shortcut_action(&self.nodes[ROOT].state, config) {
+            return Ok(Some(action));
+        }
+        let mut rng = default_rng();
+        for _ in 0..config.n_simulations {
+            let leaf = self.select_node(config);
+            let results = rollout(&self.nodes[leaf].state, config, &mut rng);
+            self.backpropagate(leaf, &results);
+        }
+        Ok(final_action(&self.nodes[ROOT].state, &self.child_stats(), config, &mut rng))
+    }
+
+    // Stats of the root's children, as in Node::child_stats()
+    fn child_stats(&self) -> Vec<ChildStat<State::Action>> {
+        let root = &self.nodes[ROOT];
+        let player = root.state.get_player_index();
+        root.children.iter()
+            .filter_map(|&child| {
+                let child = &self.nodes[child];
+                child.parent_action.map(|action| ChildStat {
+                    action,
+                    visit_count: child.visit_count,
+                    mean_value: mean_value(&child.total_values, player, child.visit_count).unwrap_or(0.),
+                    prior: root.action_probs.get(&action).copied().unwrap_or(0.),
+                })
+            })
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::FinalSelection;
+    use crate::ttt;
+
+    fn after(moves: &[(usize, usize)]) -> ttt::State {
+        moves.iter().fold(ttt::State::new(), |state, &action| state.get_next_state(action))
+    }
+
+    #[test]
+    fn configs_that_change_the_tree_are_rejected() {
+        let mut arena = Arena::new(ttt::State::new());
+        let max_nodes = SearchConfig { max_nodes: Some(100), ..SearchConfig::default() };
+        assert_eq!(arena.best_action_with_config(&max_nodes), Err(ConfigError("the arena doesn't support max_nodes".to_string())));
+        let expand_one = SearchConfig { expand_one: true, ..SearchConfig::default() };
+        assert_eq!(arena.best_action_with_config(&expand_one), Err(ConfigError("the arena doesn't support expand_one".to_string())));
+        assert!(arena.best_action_with_config(&SearchConfig { n_simulations: 10, ..SearchConfig::default() }).unwrap().is_some());
+    }
+
+    #[test]
+    fn the_final_move_follows_the_config() {
+        // X has a win at (0, 2), so by visits or by value the search plays it, unless it blunders
+        let state = after(&[(0, 0), (1, 0), (0, 1), (1, 1)]);
+        for final_selection in [FinalSelection::MaxVisits, FinalSelection::MaxValue { min_visits: 10 }] {
+            let config = SearchConfig { n_simulations: 300, final_selection, ..SearchConfig::default() };
+            assert_eq!(Arena::new(state.clone()).best_action_with_config(&config).unwrap(), Some((0, 2)));
+        }
+
+        let blunder = SearchConfig { n_simulations: 1, blunder_rate: 1., ..SearchConfig::default() };
+        let moves: std::collections::HashSet<_> = (0..50)
+            .map(|_| Arena::new(state.clone()).best_action_with_config(&blunder).unwrap().unwrap())
+            .collect();
+        assert!(moves.len() > 1);
+
+        // The first move is sampled by visits while in the opening, so it varies
+        let opening = SearchConfig { n_simulations: 20, opening_moves: 1, ..SearchConfig::default() };
+        let moves: std::collections::HashSet<_> = (0..50)
+            .map(|_| Arena::new(ttt::State::new()).best_action_with_config(&opening).unwrap().unwrap())
+            .collect();
+        assert!(moves.len() > 1);
+    }
+
+    #[test]
+    fn exact_endgames_need_no_simulations() {
+        let state = after(&[(0, 0), (1, 0), (0, 1), (1, 1)]);
+        let config = SearchConfig { n_simulations: 0, endgame_exact_depth: Some(9), ..SearchConfig::default() };
+        assert_eq!(Arena::new(state.clone()).best_action_with_config(&config).unwrap(), Some((0, 2)));
+        let config = SearchConfig { n_simulations: 0, ..SearchConfig::default() };
+        assert_eq!(Arena::new(state).best_action_with_config(&config).unwrap(), None);
+    }
+}
@@ -1,191 +1,728 @@
-use crate::mcts::{Node, GameState};
-use std::io::stdout;
+use crate::mcts::{GameState, GameStatus};
+use crate::notation::parse_number;
+use crate::player::Player;
+#[cfg(feature = "serde")]
+use crate::player::StoredPlayer;
+#[cfg(feature = "tui")]
+use crate::mcts::Node;
+#[cfg(feature = "tui")]
+use crate::ui::{self, Renderer, InputHandler};
+#[cfg(all(feature = "serde", feature = "tui"))]
+use crate::save;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "tui")]
+use std::time::Duration;
+#[cfg(feature = "tui")]
 use crossterm::{
-    cursor::{Show, Hide, MoveTo},
-    event::{read, Event, KeyCode},
-    execute,
-    terminal::{self, Clear, ClearType},
+    event::KeyCode,
+    style::{Color, ResetColor, SetForegroundColor},
     Result,
 };
 
 
-#[derive(Clone)]
-struct State {
+const DIRECTIONS: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+const DEFAULT_SYMBOLS: [char; 2] = ['X', 'O'];
+
+#[cfg(feature = "serde")]
+fn default_symbols() -> [char; 2] {
+    DEFAULT_SYMBOLS
+}
+
+#[cfg(all(feature = "serde", feature = "tui"))]
+const SAVE_PATH: &str = "connect4_save.json";
+
+// Why State::from_board() rejected a board
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionError(pub String);
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid position: {}", self.0)
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+#[derive(Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(from = "SavedState"))]
+pub struct State {
     board: Vec<Vec<char>>,
-    player: char,
+    player: Player,
+    winner: Option<Player>,  // Cached when the state is built, since the search asks for the result over and over
+    last_move: Option<(usize, usize)>,
+    connect_len: usize,
+    symbols: [char; 2],  // The first player's mark, then the second's
+    history: Option<Vec<usize>>,
+    // The columns that aren't full, cached like the winner. Saves leave it out, so a loaded state works them out
+    // from the board until its first move.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    legal_actions: Option<Vec<usize>>,
+}
+
+// A State as it's read from a save, which may be from before symbols, history or Player
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SavedState {
+    board: Vec<Vec<char>>,
+    player: StoredPlayer,
+    winner: Option<StoredPlayer>,
+    last_move: Option<(usize, usize)>,
+    connect_len: usize,
+    #[serde(default = "default_symbols")]
+    symbols: [char; 2],
+    #[serde(default)]
+    history: Option<Vec<usize>>,
+}
+
+#[cfg(feature = "serde")]
+impl From<SavedState> for State {
+    fn from(saved: SavedState) -> State {
+        State {
+            board: saved.board,
+            player: saved.player.resolve(saved.symbols),
+            winner: saved.winner.map(|winner| winner.resolve(saved.symbols)),
+            last_move: saved.last_move,
+            connect_len: saved.connect_len,
+            symbols: saved.symbols,
+            history: saved.history,
+            legal_actions: None,
+        }
+    }
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
 }
 
 impl State {
-    fn new() -> State {
+    pub fn new() -> State {
+        State::with_dimensions(6, 7, 4)
+    }
+
+    // An empty `rows` x `cols` board where `connect_len` in a row wins
+    pub fn with_dimensions(rows: usize, cols: usize, connect_len: usize) -> State {
+        assert!(rows > 0 && cols > 0 && connect_len > 0, "board dimensions and line length must be positive");
         State {
-            board: vec![vec![' '; 7]; 6],
-            player: 'X',
+            board: vec![vec![' '; cols]; rows],
+            player: Player::X,
+            winner: None,
+            last_move: None,
+            connect_len,
+            symbols: DEFAULT_SYMBOLS,
+            history: None,
+            legal_actions: Some((0..cols).collect()),
+        }
+    }
+
+    // A position to start from, given as rows from the top down with '.' for an empty cell and X or O for a piece,
+    // e.g. [".......", "...O...", "..XXO.."]. `player` is the side to move, which has to agree with the piece
+    // counts since X moves first. Four in a row wins.
+    pub fn from_board(rows: &[&str], player: char) -> std::result::Result<State, PositionError> {
+        State::from_board_with_connect(rows, player, 4)
+    }
+
+    fn from_board_with_connect(rows: &[&str], player: char, connect_len: usize) -> std::result::Result<State, PositionError> {
+        let error = |message: String| Err(PositionError(message));
+        let cols = rows.first().map_or(0, |row| row.chars().count());
+        if cols == 0 {
+            return error("the board needs at least one row and column".to_string());
+        }
+        if rows.iter().any(|row| row.chars().count() != cols) {
+            return error("every row needs the same number of cells".to_string());
+        }
+        let Some(player) = Player::from_symbol(player) else {
+            return error(format!("the player to move must be X or O, not {:?}", player));
+        };
+
+        // The board is stored bottom row first
+        let mut state = State::with_dimensions(rows.len(), cols, connect_len);
+        for (row, line) in rows.iter().rev().enumerate() {
+            for (col, cell) in line.chars().enumerate() {
+                state.board[row][col] = match cell {
+                    '.' => ' ',
+                    'X' | 'O' => cell,
+                    _ => return error(format!("unexpected {:?} in row {}, expected '.', 'X' or 'O'", cell, rows.len() - row)),
+                };
+            }
+        }
+        for col in 0..cols {
+            if let Some(row) = state.get_top_row(col) {
+                if state.board[row..].iter().any(|cells| cells[col] != ' ') {
+                    return error(format!("column {} has a piece above an empty cell", col + 1));
+                }
+            }
+        }
+        state.legal_actions = Some(state.open_columns());
+
+        let count = |piece: char| state.board.iter().flatten().filter(|&&cell| cell == piece).count();
+        let (x, o) = (count('X'), count('O'));
+        if x != o && x != o + 1 {
+            return error(format!("X has {} pieces and O {}, but X moves first and they take turns", x, o));
+        }
+        let to_move = if x == o { Player::X } else { Player::O };
+        if player != to_move {
+            return error(format!("with {} X and {} O pieces it's {} to move, not {}", x, o, to_move, player));
+        }
+        state.player = player;
+
+        // A finished game is fine, as long as only the player who moved last has a line
+        let lines: Vec<(usize, usize, Player)> = (0..rows.len())
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .filter_map(|(row, col)| {
+                let piece = Player::from_symbol(state.board[row][col])?;
+                state.completes_line(row, col, piece.symbol()).then_some((row, col, piece))
+            })
+            .collect();
+        if let Some(&(row, col, winner)) = lines.first() {
+            if lines.iter().any(|&(_, _, piece)| piece != winner) {
+                return error("both players have a line".to_string());
+            }
+            if winner == player {
+                return error(format!("{} has a line, so the game was over before {}'s last move", winner, winner.opponent()));
+            }
+            state.winner = Some(winner);
+            state.last_move = Some((row, col));
         }
+        Ok(state)
+    }
+
+    // Starts recording the moves played from here on, which history() then returns. Off by default, since
+    // every state the search creates would otherwise carry its own copy of the list.
+    pub fn with_history(mut self) -> State {
+        self.history.get_or_insert_with(Vec::new);
+        self
+    }
+
+    // The moves played since with_history() was called, or None if it never was
+    pub fn history(&self) -> Option<&[usize]> {
+        self.history.as_deref()
+    }
+
+    // Swaps in other marks for the two players, e.g. ['●', '○'], relabelling any pieces already on the board
+    pub fn with_symbols(mut self, symbols: [char; 2]) -> State {
+        assert!(symbols[0] != symbols[1] && !symbols.contains(&' '), "player symbols must be distinct and not blank");
+        let relabel = |cell: char| match cell {
+            ' ' => ' ',
+            cell if cell == self.symbols[0] => symbols[0],
+            _ => symbols[1],
+        };
+        for row in self.board.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = relabel(*cell);
+            }
+        }
+        self.symbols = symbols;
+        self
+    }
+
+    // The mark of the player whose get_player_turn() is `player`
+    pub fn symbol(&self, player: i32) -> char {
+        if player == 1 { self.symbols[0] } else { self.symbols[1] }
+    }
+
+    fn mark(&self, player: Player) -> char {
+        self.symbols[player.index()]
+    }
+
+    // The cells of the line completed by the winning move, if the game has been won
+    pub fn winning_cells(&self) -> Option<Vec<(usize, usize)>> {
+        let winner = self.mark(self.winner?);
+        let (row, col) = self.last_move?;
+        let (rows, cols) = (self.board.len() as isize, self.board[0].len() as isize);
+        DIRECTIONS.iter().find_map(|&(dr, dc)| {
+            let mut cells = vec![(row, col)];
+            for sign in [1, -1] {
+                let (mut r, mut c) = (row as isize + sign * dr, col as isize + sign * dc);
+                while r >= 0 && r < rows && c >= 0 && c < cols && self.board[r as usize][c as usize] == winner {
+                    cells.push((r as usize, c as usize));
+                    r += sign * dr;
+                    c += sign * dc;
+                }
+            }
+            if cells.len() >= self.connect_len { Some(cells) } else { None }
+        })
+    }
+
+    fn top_row_full(&self, col: usize) -> bool {
+        self.board[self.board.len() - 1][col] != ' '
+    }
+
+    fn open_columns(&self) -> Vec<usize> {
+        (0..self.board[0].len()).filter(|&col| !self.top_row_full(col)).collect()
+    }
+
+    // The board is full once the top row is
+    fn board_full(&self) -> bool {
+        self.legal_actions.as_ref().map_or_else(|| self.open_columns().is_empty(), Vec::is_empty)
     }
 
     fn get_top_row(&self, col: usize) -> Option<usize> {
         self.board.iter().position(|row| row[col] == ' ')
     }
+
+    // Whether a piece dropped into `col` by `player` (as in get_player_turn()) would complete a line, whoever's
+    // turn it actually is. False for a full column.
+    pub fn threatens_win(&self, player: i32, col: usize) -> bool {
+        self.get_top_row(col).is_some_and(|row| self.completes_line(row, col, self.symbol(player)))
+    }
+
+    // Whether a piece of `player` at (row, col) would be part of `connect_len` in a row
+    fn completes_line(&self, row: usize, col: usize, player: char) -> bool {
+        let (rows, cols) = (self.board.len() as isize, self.board[0].len() as isize);
+        DIRECTIONS.iter().any(|&(dr, dc)| {
+            let count_from = |sign: isize| {
+                let mut count = 0;
+                let (mut r, mut c) = (row as isize + sign * dr, col as isize + sign * dc);
+                while r >= 0 && r < rows && c >= 0 && c < cols && self.board[r as usize][c as usize] == player {
+                    count += 1;
+                    r += sign * dr;
+                    c += sign * dc;
+                }
+                count
+            };
+            1 + count_from(1) + count_from(-1) >= self.connect_len
+        })
+    }
+
+    // status() worked out from the whole board rather than the cached winner, the way every call did before the
+    // winner was cached. For checking the cache against, and for timing what it saves.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn scanned_status(&self) -> GameStatus {
+        let (rows, cols) = (self.board.len(), self.board[0].len());
+        for row in 0..rows {
+            for col in 0..cols {
+                let piece = self.board[row][col];
+                if piece != ' ' && self.completes_line(row, col, piece) {
+                    return GameStatus::Win(if piece == self.symbols[0] { 1 } else { -1 });
+                }
+            }
+        }
+        if self.open_columns().is_empty() { GameStatus::Draw } else { GameStatus::InProgress }
+    }
 }
 
 impl GameState for State {
     type Action = usize;
 
+    fn status(&self) -> GameStatus {
+        match self.winner {
+            Some(player) => GameStatus::Win(player.value()),
+            None if self.board_full() => GameStatus::Draw,
+            None => GameStatus::InProgress,
+        }
+    }
+
+    // The same answer as status(), without working out who won
     fn is_terminal(&self) -> bool {
-        self.get_result() != 0 || self.board.iter().all(|row| row.iter().all(|&cell| cell != ' '))
+        self.winner.is_some() || self.board_full()
     }
 
     fn get_player_turn(&self) -> i32 {
-        match self.player {
-            'X' => 1,
-            'O' => -1,
-            _ => unreachable!(),
-        }
+        self.player.value()
+    }
+
+    fn move_count(&self) -> Option<usize> {
+        Some(self.board.iter().map(|row| row.iter().filter(|&&cell| cell != ' ').count()).sum())
     }
 
     fn get_legal_actions(&self) -> Vec<Self::Action> {
-        (0..self.board[0].len())
-            .filter(|&col| self.board[5][col] == ' ')
-            .collect()
+        self.legal_actions.clone().unwrap_or_else(|| self.open_columns())
     }
 
-    fn get_policy(&self, actions: &Vec<Self::Action>) -> Vec<f64> {
+    fn get_policy(&self, actions: &[Self::Action]) -> Vec<f64> {
+        // Favour winning moves, then moves that block the opponent's win
+        let player = self.get_player_turn();
         let mut scores = Vec::new();
         for &action in actions {
-            let mut next_state = self.get_next_state(action);
             let mut score = 100.0;
-            if next_state.get_result() != 0 { score += 300.0; }
-            next_state = self.clone();
-            next_state.player = if self.player == 'X' { 'O' } else { 'X' };
-            next_state = next_state.get_next_state(action);
-            if next_state.get_result() != 0 { score += 200.0; }
+            if self.threatens_win(player, action) { score += 300.0; }
+            if self.threatens_win(-player, action) { score += 200.0; }
             scores.push(score);
         }
 
         // Return the softmax of the scores
         let max_score = scores.iter().fold(f64::MIN, |a, &b| a.max(b));
-        let exp_scores: Vec<f64> = scores.iter().map(|s| ((s - max_score) as f64).exp()).collect();
+        let exp_scores: Vec<f64> = scores.iter().map(|s| (s - max_score).exp()).collect();
         let sum_exp_scores: f64 = exp_scores.iter().sum();
         exp_scores.iter().map(|s| s / sum_exp_scores).collect()
     }
 
+    // The player to move is winning if they can complete a line now, and losing if they can't but the opponent has
+    // two places to, since only one can be blocked. Anything else is scored as even.
+    fn evaluate(&self) -> f64 {
+        let player = self.get_player_turn();
+        let cols = 0..self.board[0].len();
+        if cols.clone().any(|col| self.threatens_win(player, col)) {
+            0.9 * player as f64
+        } else if cols.filter(|&col| self.threatens_win(-player, col)).count() >= 2 {
+            -0.9 * player as f64
+        } else {
+            0.
+        }
+    }
+
     fn get_next_state(&self, action: Self::Action) -> State {
         let mut next_board = self.board.clone();
-        if let Some(row) = self.get_top_row(action) {
-            next_board[row][action] = self.player;
+        let last_move = self.get_top_row(action).map(|row| (row, action));
+        let mut legal_actions = self.get_legal_actions();
+        if let Some((row, col)) = last_move {
+            next_board[row][col] = self.mark(self.player);
+            // Only the column played in can have filled up
+            if row == self.board.len() - 1 {
+                legal_actions.retain(|&open_col| open_col != col);
+            }
         }
-        let next_player = if self.player == 'X' { 'O' } else { 'X' };
-        State {
+        let mut next_state = State {
             board: next_board,
-            player: next_player,
+            player: self.player.opponent(),
+            winner: self.winner,
+            last_move,
+            connect_len: self.connect_len,
+            symbols: self.symbols,
+            history: self.history.as_ref().map(|history| [history.as_slice(), &[action]].concat()),
+            legal_actions: Some(legal_actions),
+        };
+
+        // Only the piece just dropped can complete a line, so there's no need to rescan the board
+        if let Some((row, col)) = last_move {
+            if next_state.completes_line(row, col, self.mark(self.player)) {
+                next_state.winner = Some(self.player);
+            }
         }
+        next_state
+    }
+
+    // "c" and the column number from 1 at the left, e.g. "c4"
+    fn action_to_string(&self, col: &Self::Action) -> String {
+        format!("c{}", col + 1)
+    }
+
+    fn parse_action(&self, text: &str) -> Option<Self::Action> {
+        let number = parse_number(text.strip_prefix('c')?)?;
+        (1..=self.board[0].len()).contains(&number).then(|| number - 1)
+    }
+
+    fn hash_key(&self) -> u64 {
+        // The winner follows from the board, and the last move and history shouldn't split transpositions
+        let mut hasher = DefaultHasher::new();
+        self.board.hash(&mut hasher);
+        self.player.hash(&mut hasher);
+        hasher.finish()
     }
 
-    fn get_result(&self) -> i32 {
-        // Check horizontal
-        for row in &self.board {
-            for col in 0..4 {
-                if row[col] != ' ' && row[col] == row[col + 1] && row[col] == row[col + 2] && row[col] == row[col + 3] {
-                    return -1;
+    // The smaller hash of the board and its left-right mirror image
+    fn canonical_key(&self) -> u64 {
+        let mirrored: Vec<Vec<char>> = self.board.iter().map(|row| row.iter().rev().copied().collect()).collect();
+        let mut hasher = DefaultHasher::new();
+        mirrored.hash(&mut hasher);
+        self.player.hash(&mut hasher);
+        self.hash_key().min(hasher.finish())
+    }
+}
+
+
+#[cfg(feature = "tui")]
+struct BoardRenderer;
+
+#[cfg(feature = "tui")]
+impl Renderer<State> for BoardRenderer {
+    fn render(&self, state: &State, cursor: usize, hint: Option<usize>) {
+        let cols = state.board[0].len();
+        let winning_cells = state.winning_cells().unwrap_or_default();
+        let separator = format!("{}|", "|---".repeat(cols));
+        let markers = (0..cols).map(|i| {
+            let inside = if hint == Some(i) { format!("{}*{}", SetForegroundColor(Color::Yellow), ResetColor) } else { " ".to_string() };
+            if i == cursor { format!(" [{}]", inside) } else { format!("  {} ", inside) }
+        });
+        println!("{}\r", markers.collect::<String>());
+        println!("{}\r", separator);
+        for (i, row) in state.board.iter().enumerate().rev() {
+            let cells = row.iter().enumerate().map(|(j, cell)| {
+                if winning_cells.contains(&(i, j)) {
+                    format!("| {}{}{} ", SetForegroundColor(Color::Green), cell, ResetColor)
+                } else {
+                    format!("| {} ", cell)
                 }
-            }
+            });
+            println!("{}|\r", cells.collect::<String>());
+            println!("{}\r", separator);
         }
+        println!("{}\r", (1..=cols).map(|i| format!("{:>3} ", i)).collect::<String>().trim_end());
+    }
 
-        // Check vertical
-        for row in 0..3 {
-            for col in 0..self.board[0].len() {
-                if self.board[row][col] != ' ' && self.board[row][col] == self.board[row + 1][col] && self.board[row][col] == self.board[row + 2][col] && self.board[row][col] == self.board[row + 3][col] {
-                    return -1;
-                }
-            }
+    fn player_symbol(&self, state: &State, player: i32) -> char {
+        state.symbol(player)
+    }
+
+    // Columns are numbered from 1 under the board
+    fn action_label(&self, col: usize) -> String {
+        (col + 1).to_string()
+    }
+}
+
+#[cfg(feature = "tui")]
+struct KeyHandler {
+    cols: usize,
+}
+
+#[cfg(feature = "tui")]
+impl InputHandler<State> for KeyHandler {
+    fn initial_cursor(&self) -> usize {
+        0
+    }
+
+    fn move_cursor(&self, col: usize, key: KeyCode) -> Option<usize> {
+        match key {
+            KeyCode::Left => Some(col.saturating_sub(1)),
+            KeyCode::Right if col < self.cols - 1 => Some(col + 1),
+            _ => None,
         }
+    }
 
-        // Check diagonal (down-right and down-left)
-        for row in 0..3 {
-            for col in 0..7 {
-                if col <= 3 && self.board[row][col] != ' ' && self.board[row][col] == self.board[row + 1][col + 1] && self.board[row][col] == self.board[row + 2][col + 2] && self.board[row][col] == self.board[row + 3][col + 3] {
-                    return -1;
-                }
-                if col >= 3 && self.board[row][col] != ' ' && self.board[row][col] == self.board[row + 1][col - 1] && self.board[row][col] == self.board[row + 2][col - 2] && self.board[row][col] == self.board[row + 3][col - 3] {
-                    return -1;
+    // Each column is four characters wide, so a click anywhere in one drops a piece there
+    fn click(&self, column: u16, _row: u16) -> Option<usize> {
+        let col = column as usize / 4;
+        if col < self.cols { Some(col) } else { None }
+    }
+
+    #[cfg(feature = "serde")]
+    fn save(&self, state: &State) -> Result<()> {
+        save::save_game(state, SAVE_PATH)
+    }
+}
+
+
+// Reads a position for from_board() from a file with one row per line. Whose turn it is follows from the piece counts.
+#[cfg(feature = "tui")]
+fn load_position(path: &str, connect_len: usize) -> Result<State> {
+    let text = std::fs::read_to_string(path)?;
+    let rows: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    let pieces = |piece: char| rows.iter().map(|row| row.matches(piece).count()).sum::<usize>();
+    let player = if pieces('X') > pieces('O') { 'O' } else { 'X' };
+    State::from_board_with_connect(&rows, player, connect_len).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(feature = "tui")]
+pub fn play(n_simulations: Option<i32>, options: ui::Options, symbols: Option<[char; 2]>, load_path: Option<&str>, position_path: Option<&str>, (rows, cols, connect_len): (usize, usize, usize)) -> Result<()> {
+    let mut initial_state: State = match (load_path, position_path) {
+        #[cfg(feature = "serde")]
+        (Some(path), _) => save::load_game(path)?,
+        (_, Some(path)) => load_position(path, connect_len)?,
+        _ => State::with_dimensions(rows, cols, connect_len),
+    };
+    if let Some(symbols) = symbols {
+        initial_state = initial_state.with_symbols(symbols);
+    }
+    let key_handler = KeyHandler { cols: initial_state.board[0].len() };
+    let options = ui::Options { simulations: n_simulations, ..options };
+    ui::run_game(initial_state, options, &BoardRenderer, &key_handler, |root, simulations| {
+        // Without a level or a change with +/-, think for a fixed time rather than a fixed number of simulations
+        match simulations {
+            Some(n_simulations) => Node::best_action(root, n_simulations),
+            None => Node::best_action_timed(root, Duration::from_millis(500)),
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    fn after(moves: &[usize]) -> State {
+        moves.iter().fold(State::new(), |state, &action| state.get_next_state(action))
+    }
+
+    #[test]
+    fn get_result_reports_the_winner() {
+        let x_wins = after(&[0, 1, 0, 1, 0, 1, 0]);
+        assert_eq!(x_wins.status(), GameStatus::Win(1));
+        assert_eq!(x_wins.get_result(), 1.);
+
+        let o_wins = after(&[0, 1, 0, 1, 0, 1, 2, 1]);
+        assert_eq!(o_wins.status(), GameStatus::Win(-1));
+        assert_eq!(o_wins.get_result(), -1.);
+
+        let full_board = ["OOXXOOX", "OOXXOOX", "XXOOXXO", "OOXXOOX", "XXOOXXO", "XXOOXXO"];
+        let draw = State::from_board(&full_board, 'X').unwrap();
+        assert_eq!(draw.status(), GameStatus::Draw);
+        assert_eq!(draw.get_result(), 0.);
+
+        assert_eq!(State::new().get_result(), 0.);
+    }
+
+    // The prior of each column, which has to be a distribution
+    fn policy(state: &State) -> Vec<f64> {
+        let actions = state.get_legal_actions();
+        let policy = state.get_policy(&actions);
+        assert!((policy.iter().sum::<f64>() - 1.).abs() < 1e-9);
+        policy
+    }
+
+    fn argmax(values: &[f64]) -> usize {
+        (0..values.len()).max_by(|&a, &b| values[a].total_cmp(&values[b])).unwrap()
+    }
+
+    #[test]
+    fn the_policy_favours_a_winning_drop() {
+        // X has three in column 0, and O three in column 1, so X should win rather than block
+        let state = after(&[0, 1, 0, 1, 0, 1]);
+        let policy = policy(&state);
+        assert_eq!(argmax(&policy), 0);
+        assert!(policy[1] > policy[2]);
+    }
+
+    #[test]
+    fn the_policy_favours_a_block() {
+        // O to move, with X threatening column 0
+        let state = after(&[0, 1, 0, 1, 0]);
+        assert_eq!(argmax(&policy(&state)), 0);
+        assert!(policy(&State::new()).windows(2).all(|pair| pair[0] == pair[1]));
+    }
+
+    #[test]
+    fn the_cached_status_and_columns_follow_each_move() {
+        // Column 3 fills up on the sixth drop, then X completes the bottom row
+        let moves = [3, 3, 3, 3, 3, 3, 0, 0, 1, 1, 2];
+        let mut state = State::new();
+        for &col in moves.iter() {
+            state = state.get_next_state(col);
+            assert_eq!(state.status(), state.scanned_status());
+            assert_eq!(state.get_legal_actions(), state.open_columns());
+        }
+        assert!(!state.get_legal_actions().contains(&3));
+        assert_eq!(state.status(), GameStatus::Win(1));
+    }
+
+    #[test]
+    fn the_last_move_check_agrees_with_a_full_scan() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (rows, cols, connect_len) in [(6, 7, 4), (4, 5, 3), (8, 9, 5)] {
+            for _ in 0..200 {
+                let mut state = State::with_dimensions(rows, cols, connect_len);
+                while !state.is_terminal() {
+                    let &col = state.get_legal_actions().choose(&mut rng).unwrap();
+                    state = state.get_next_state(col);
+                    assert_eq!(state.status(), state.scanned_status());
                 }
             }
         }
+    }
+
+    #[test]
+    fn five_in_a_row_wins_on_a_wider_board() {
+        let mut state = State::with_dimensions(8, 9, 5);
+        assert_eq!(state.get_legal_actions(), (0..9).collect::<Vec<_>>());
+        // X along the bottom row from column 4 to column 8, O stacking on top
+        for col in 4..8 {
+            state = state.get_next_state(col).get_next_state(col);
+        }
+        assert_eq!(state.status(), GameStatus::InProgress, "four in a row isn't enough");
+        state = state.get_next_state(8);
+        assert_eq!(state.status(), GameStatus::Win(1));
+        assert_eq!(state.winning_cells().unwrap().len(), 5);
+
+        let mut state = State::with_dimensions(8, 9, 5);
+        for _ in 0..8 {
+            state = state.get_next_state(0);
+        }
+        assert!(!state.get_legal_actions().contains(&0), "a column fills after eight pieces");
+    }
 
-        0 // Draw or no winner yet
+    #[test]
+    fn a_full_board_is_a_draw_rather_than_in_progress() {
+        let one_left = State::from_board(&[".OXXOOX", "OOXXOOX", "XXOOXXO", "OOXXOOX", "XXOOXXO", "XXOOXXO"], 'O').unwrap();
+        assert_eq!(one_left.status(), GameStatus::InProgress);
+        assert!(!one_left.is_terminal());
+        assert_eq!(one_left.get_legal_actions(), vec![0]);
+        let full = one_left.get_next_state(0);
+        assert_eq!(full.status(), GameStatus::Draw);
+        assert!(full.is_terminal());
+        assert_eq!(one_left.get_result(), full.get_result());
     }
-}
 
+    #[test]
+    fn mirrored_boards_share_a_canonical_key() {
+        let state = after(&[0, 1, 1, 3]);
+        let mirrored = after(&[6, 5, 5, 3]);
+        assert_ne!(state.hash_key(), mirrored.hash_key());
+        assert_eq!(state.canonical_key(), mirrored.canonical_key());
+        assert_ne!(state.canonical_key(), after(&[0, 1, 1, 2]).canonical_key());
+
+        // Wider boards mirror about their own middle column
+        let wide = |moves: &[usize]| moves.iter().fold(State::with_dimensions(8, 9, 5), |state, &col| state.get_next_state(col));
+        assert_eq!(wide(&[0, 2]).canonical_key(), wide(&[8, 6]).canonical_key());
+    }
+
+    #[test]
+    fn threatens_win_sees_both_players_lines() {
+        // X to move with three in column 0, and O three in column 1
+        let state = after(&[0, 1, 0, 1, 0, 1]);
+        assert!(state.threatens_win(1, 0), "X wins here");
+        assert!(state.threatens_win(-1, 1), "O would win here on its turn");
+        assert!(!state.threatens_win(1, 1));
+        assert!(!state.threatens_win(-1, 0));
+        assert!(!(2..7).any(|col| state.threatens_win(1, col) || state.threatens_win(-1, col)));
+
+        // A line across the bottom, and a full column never threatens anything
+        let state = after(&[1, 1, 2, 2, 3]);
+        assert!(state.threatens_win(1, 0) && state.threatens_win(1, 4));
+        let full_column = after(&[0, 0, 0, 0, 0, 0]);
+        assert!(!full_column.threatens_win(1, 0) && !full_column.threatens_win(-1, 0));
+    }
 
-pub fn play() -> Result<()> {
-    // Terminal setup
-    let mut stdout = stdout();
-    terminal::enable_raw_mode()?;
-    execute!(stdout, Hide, Clear(ClearType::All))?;
-
-    // Game state initialization
-    let mut root = Node::new(State::new(), None, None);
-    let mut current_pos: <State as GameState>::Action = 0;
-
-    // Main game loop
-    loop {
-        execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
-        println!("{}\r", (0..7).map(|i| if i == current_pos { " [ ]" } else { "    " }).collect::<String>());
-        println!("|---|---|---|---|---|---|---|\r");
-        for row in root.borrow().state.board.iter().rev() {
-            println!("| {} | {} | {} | {} | {} | {} | {} |\r", row[0], row[1], row[2], row[3], row[4], row[5], row[6]);
-            println!("|---|---|---|---|---|---|---|\r");
-        }
-        println!("  1   2   3   4   5   6   7");
-
-        if root.borrow().state.is_terminal() {
-            break;
-        }
-
-        if root.borrow().state.player == 'X' {
-            // Human turn
-            if let Event::Key(key_event) = read()? {
-                match key_event.code {
-                    KeyCode::Left => if current_pos > 0 { current_pos -= 1; },
-                    KeyCode::Right => if current_pos < 6 { current_pos += 1; },
-                    KeyCode::Enter | KeyCode::Char(' ') => {
-                        if root.borrow().state.get_legal_actions().contains(&current_pos) {
-                            let next_state = root.borrow().state.get_next_state(current_pos);
-                            root = Node::new(next_state, None, Some(current_pos));
-                        }
-                    },
-                    KeyCode::Esc => break,
-                    _ => {}
+    #[test]
+    fn is_terminal_agrees_with_status() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..300 {
+            let mut state = State::new();
+            loop {
+                assert_eq!(state.is_terminal(), state.status() != GameStatus::InProgress);
+                if state.is_terminal() {
+                    break;
                 }
+                state = state.get_next_state(*state.get_legal_actions().choose(&mut rng).unwrap());
             }
-        } else {
-            // AI turn
-            let action = Node::best_action(&root, 1000);
-            let next_state = root.borrow().state.get_next_state(action);
-            root = Node::new(next_state, None, Some(action));
-        }
-    }
-
-    let state = &root.borrow().state;
-    let result_message = match state.get_result() {
-        -1 => match state.player {
-            'O' => "Player X wins!",
-            'X' => "Player O wins!",
-            _ => unreachable!(),
-        },
-        0 => "It's a draw!",
-        _ => unreachable!(),
-    };
-
-    if state.is_terminal() {
-        execute!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
-        println!("{}", result_message);
-        loop {
-            if let Event::Key(_) = read()? { break; }
         }
     }
 
-    terminal::disable_raw_mode()?;
-    execute!(stdout, Show, MoveTo(0, 0), Clear(ClearType::All))?;
-    Ok(())
+    #[test]
+    fn from_board_reads_a_position() {
+        let rows = [".......", ".......", ".......", ".......", "...O...", "..XXO.."];
+        let state = State::from_board(&rows, 'X').unwrap();
+        assert_eq!(state.hash_key(), after(&[2, 4, 3, 3]).hash_key());
+        assert_eq!(state.get_player_turn(), 1);
+        assert_eq!(state.status(), GameStatus::InProgress);
+
+        // A won game loads as won, with the winning line found
+        let won = State::from_board(&[".......", ".......", "X......", "X......", "XO.....", "XOO...."], 'O').unwrap();
+        assert_eq!(won.status(), GameStatus::Win(1));
+        assert_eq!(won.winning_cells().map(|cells| cells.len()), Some(4));
+    }
+
+    #[test]
+    fn from_board_rejects_impossible_positions() {
+        let rejects = |rows: &[&str], player: char, reason: &str| match State::from_board(rows, player) {
+            Err(PositionError(message)) => assert!(message.contains(reason), "{:?} doesn't mention {:?}", message, reason),
+            Ok(_) => panic!("{:?} was accepted", rows),
+        };
+        let empty = [".......", ".......", ".......", ".......", ".......", "......."];
+        rejects(&[], 'X', "at least one row");
+        rejects(&[".......", "......"], 'X', "same number of cells");
+        rejects(&empty, 'Z', "must be X or O");
+        rejects(&[".......", ".......", ".......", ".......", ".......", "...x..."], 'X', "unexpected 'x'");
+        rejects(&[".......", ".......", ".......", "...X...", ".......", "...O..."], 'O', "column 4 has a piece above an empty cell");
+        rejects(&[".......", ".......", ".......", ".......", ".......", "..XXX.."], 'O', "X has 3 pieces and O 0");
+        rejects(&[".......", ".......", ".......", ".......", ".......", "..XO..."], 'O', "it's X to move");
+        rejects(&[".......", ".......", "XO.....", "XO.....", "XO.....", "XO....."], 'X', "both players have a line");
+        rejects(&[".......", ".......", "X......", "X......", "XO.....", "XOO..O."], 'X', "X has a line, so the game was over");
+    }
 }
@@ -0,0 +1,206 @@
+use crate::mcts::{GameState, GameStatus, sample_index};
+use rand::prelude::*;
+use std::collections::HashSet;
+
+// Sanity checks for a GameState implementation, for the mistakes that make the search quietly play for the wrong
+// side: a winner whose get_result() has the wrong sign, a get_player_turn() that never changes hands, and priors
+// that aren't usable weights. Whether a game's winning condition is right can't be checked without knowing its
+// rules, so these only check that the state agrees with itself.
+
+const GAMES: usize = 20;
+const MAX_MOVES: usize = 1000;
+
+struct Problems {
+    found: Vec<String>,
+    kinds: HashSet<&'static str>,
+}
+
+impl Problems {
+    fn report(&mut self, kind: &'static str, moves: &[String], message: String) {
+        if self.kinds.insert(kind) {
+            let place = if moves.is_empty() { "at the starting position".to_string() } else { format!("after {}", moves.join(" ")) };
+            self.found.push(format!("{}: {}", place, message));
+        }
+    }
+}
+
+// Plays GAMES seeded random games from `state` and describes each kind of problem found the first time it turns
+// up, with the moves that led there. An empty list means nothing was found.
+pub fn verify_game_consistency<State: GameState + Clone>(state: &State) -> Vec<String> {
+    let mut problems = Problems { found: Vec::new(), kinds: HashSet::new() };
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for _ in 0..GAMES {
+        let mut state = state.clone();
+        let mut moves: Vec<String> = Vec::new();
+        let mut turn_changed = false;
+        while moves.len() < MAX_MOVES {
+            check_state(&state, &moves, &mut problems);
+            if state.is_terminal() {
+                break;
+            }
+
+            if state.is_chance_node() {
+                let outcomes = state.get_chance_outcomes();
+                let weights: Vec<f64> = outcomes.iter().map(|&(_, probability)| probability).collect();
+                if outcomes.is_empty() {
+                    problems.report("no outcomes", &moves, "is_chance_node() is true but get_chance_outcomes() is empty".to_string());
+                    break;
+                }
+                if weights.iter().any(|p| !p.is_finite() || *p < 0.) || (weights.iter().sum::<f64>() - 1.).abs() > 1e-6 {
+                    problems.report("outcome probabilities", &moves, format!("get_chance_outcomes() has probabilities {:?}, which don't sum to 1", weights));
+                }
+                state = outcomes[sample_index(&weights, &mut rng)].0.clone();
+                moves.push("(chance)".to_string());
+                continue;
+            }
+
+            let actions = state.get_legal_actions();
+            if actions.is_empty() {
+                problems.report("no moves", &moves, "the game isn't over but get_legal_actions() is empty".to_string());
+                break;
+            }
+            check_weights("get_policy()", &state.get_policy(&actions), actions.len(), &moves, &mut problems);
+            check_weights("get_rollout_policy()", &state.get_rollout_policy(&actions), actions.len(), &moves, &mut problems);
+
+            let action = *actions.choose(&mut rng).unwrap();
+            let next_state = state.get_next_state(action);
+            turn_changed |= next_state.get_player_turn() != state.get_player_turn();
+            moves.push(state.action_to_string(&action));
+            state = next_state;
+        }
+
+        // Games with extra turns keep the same player for a while, but never across a whole game
+        if !turn_changed && moves.len() > 1 {
+            problems.report("turn never changes", &moves, "get_player_turn() stayed the same for the whole game".to_string());
+        }
+    }
+    problems.found
+}
+
+fn check_state<State: GameState>(state: &State, moves: &[String], problems: &mut Problems) {
+    let status = state.status();
+    let turn = state.get_player_turn();
+    if state.is_terminal() != (status != GameStatus::InProgress) {
+        problems.report("is_terminal", moves, format!("is_terminal() is {} but status() is {:?}", state.is_terminal(), status));
+    }
+    if state.num_players() == 2 {
+        if turn != 1 && turn != -1 {
+            problems.report("turn value", moves, format!("get_player_turn() is {}, not 1 or -1", turn));
+        } else if state.get_player_index() != if turn == 1 { 0 } else { 1 } {
+            problems.report("player index", moves, format!("get_player_index() is {} when get_player_turn() is {}", state.get_player_index(), turn));
+        }
+    }
+    if state.get_player_index() >= state.num_players() {
+        problems.report("player index range", moves, format!("get_player_index() is {} with {} players", state.get_player_index(), state.num_players()));
+    }
+
+    if state.is_terminal() {
+        let result = state.get_result();
+        let results = state.get_results();
+        if !(-1. ..=1.).contains(&result) {
+            problems.report("result range", moves, format!("get_result() is {}, outside [-1, 1]", result));
+        }
+        match status {
+            GameStatus::Win(winner) if winner != 1 && winner != -1 && state.num_players() == 2 => {
+                problems.report("winner value", moves, format!("status() is Win({}), but the winner should be 1 or -1", winner));
+            },
+            GameStatus::Win(winner) if state.num_players() == 2 && result * winner as f64 <= 0. => {
+                problems.report("result sign", moves, format!("status() is Win({}) but get_result() is {}, which favours the other player", winner, result));
+            },
+            GameStatus::Draw if result != 0. => {
+                problems.report("draw result", moves, format!("status() is Draw but get_result() is {}", result));
+            },
+            _ => {},
+        }
+        if results.len() != state.num_players() || results.iter().any(|value| !value.is_finite()) {
+            problems.report("results", moves, format!("get_results() is {:?} with {} players", results, state.num_players()));
+        } else if let (GameStatus::Win(winner), 2) = (status, state.num_players()) {
+            let winner_index = if winner == 1 { 0 } else { 1 };
+            if results[winner_index] <= results[1 - winner_index] {
+                problems.report("results sign", moves, format!("status() is Win({}) but get_results() is {:?}", winner, results));
+            }
+        }
+    } else {
+        let value = state.evaluate();
+        if !(-1. ..=1.).contains(&value) {
+            problems.report("evaluate range", moves, format!("evaluate() is {}, outside [-1, 1]", value));
+        }
+    }
+}
+
+fn check_weights(name: &str, weights: &[f64], n_actions: usize, moves: &[String], problems: &mut Problems) {
+    if weights.len() != n_actions {
+        problems.report("weights length", moves, format!("{} gave {} weights for {} moves", name, weights.len(), n_actions));
+    } else if weights.iter().any(|weight| !weight.is_finite() || *weight < 0.) {
+        problems.report("weights finite", moves, format!("{} gave weights {:?}, which aren't all finite and >= 0", name, weights));
+    } else if weights.iter().sum::<f64>() <= 0. {
+        problems.report("weights zero", moves, format!("{} gave every move a weight of 0", name));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{connect4, dots_and_boxes, mancala, ttt};
+
+    // Tic-tac-toe with one thing broken: get_result() has its sign flipped, or get_player_turn() is always 1
+    #[derive(Clone)]
+    struct Broken {
+        state: ttt::State,
+        flipped_result: bool,
+        stuck_turn: bool,
+    }
+
+    impl GameState for Broken {
+        type Action = (usize, usize);
+
+        fn status(&self) -> GameStatus {
+            self.state.status()
+        }
+
+        fn get_player_turn(&self) -> i32 {
+            if self.stuck_turn { 1 } else { self.state.get_player_turn() }
+        }
+
+        fn get_legal_actions(&self) -> Vec<(usize, usize)> {
+            self.state.get_legal_actions()
+        }
+
+        fn get_policy(&self, actions: &[(usize, usize)]) -> Vec<f64> {
+            self.state.get_policy(actions)
+        }
+
+        fn get_next_state(&self, action: (usize, usize)) -> Broken {
+            Broken { state: self.state.get_next_state(action), ..*self }
+        }
+
+        fn hash_key(&self) -> u64 {
+            self.state.hash_key()
+        }
+
+        fn get_result(&self) -> f64 {
+            if self.flipped_result { -self.state.get_result() } else { self.state.get_result() }
+        }
+    }
+
+    #[test]
+    fn the_games_pass() {
+        assert_eq!(verify_game_consistency(&ttt::State::new()), Vec::<String>::new());
+        assert_eq!(verify_game_consistency(&connect4::State::new()), Vec::<String>::new());
+        assert_eq!(verify_game_consistency(&mancala::State::new()), Vec::<String>::new());
+        assert_eq!(verify_game_consistency(&dots_and_boxes::State::new(2, 3)), Vec::<String>::new());
+    }
+
+    #[test]
+    fn broken_games_are_caught() {
+        let flipped = Broken { state: ttt::State::new(), flipped_result: true, stuck_turn: false };
+        let problems = verify_game_consistency(&flipped);
+        assert!(problems.iter().any(|problem| problem.contains("favours the other player")), "{:?}", problems);
+
+        let stuck = Broken { state: ttt::State::new(), flipped_result: false, stuck_turn: true };
+        let problems = verify_game_consistency(&stuck);
+        assert!(problems.iter().any(|problem| problem.contains("stayed the same for the whole game")), "{:?}", problems);
+    }
+}
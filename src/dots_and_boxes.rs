@@ -0,0 +1,229 @@
+use crate::mcts::{GameState, GameStatus};
+use crate::notation::{parse_square, square};
+use crate::player::Player;
+#[cfg(feature = "tui")]
+use crate::mcts::Node;
+#[cfg(feature = "tui")]
+use crate::ui::{self, Renderer, InputHandler};
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "tui")]
+use crossterm::{
+    event::KeyCode,
+    style::{Attribute, Color, ResetColor, SetAttribute, SetForegroundColor},
+    Result,
+};
+
+
+pub const DEFAULT_SIZE: (usize, usize) = (3, 3);
+
+// The board is a lattice of (2 * rows + 1) x (2 * cols + 1) cells: dots where both coordinates are even, boxes
+// where both are odd, and edges in between. An action is the lattice position of the edge to draw.
+#[derive(Clone)]
+pub struct State {
+    edges: Vec<Vec<bool>>,
+    owners: Vec<Vec<Option<Player>>>,  // Indexed by lattice position too, only meaningful at boxes
+    player: Player,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new(DEFAULT_SIZE.0, DEFAULT_SIZE.1)
+    }
+}
+
+impl State {
+    // A board of `rows` x `cols` boxes
+    pub fn new(rows: usize, cols: usize) -> State {
+        assert!(rows > 0 && cols > 0, "the board needs at least one box");
+        State {
+            edges: vec![vec![false; 2 * cols + 1]; 2 * rows + 1],
+            owners: vec![vec![None; 2 * cols + 1]; 2 * rows + 1],
+            player: Player::X,
+        }
+    }
+
+    // Boxes claimed by `player`
+    pub fn score(&self, player: Player) -> usize {
+        self.owners.iter().map(|row| row.iter().filter(|&&owner| owner == Some(player)).count()).sum()
+    }
+
+    fn n_boxes(&self) -> usize {
+        (self.edges.len() / 2) * (self.edges[0].len() / 2)
+    }
+
+    // The boxes on either side of an edge
+    fn boxes_beside(&self, (row, col): (usize, usize)) -> Vec<(usize, usize)> {
+        let candidates = if row % 2 == 0 {
+            [(row.wrapping_sub(1), col), (row + 1, col)]
+        } else {
+            [(row, col.wrapping_sub(1)), (row, col + 1)]
+        };
+        candidates.into_iter().filter(|&(r, c)| r < self.edges.len() && c < self.edges[0].len()).collect()
+    }
+
+    // How many of a box's four edges are drawn
+    fn sides_drawn(&self, (row, col): (usize, usize)) -> usize {
+        [(row - 1, col), (row + 1, col), (row, col - 1), (row, col + 1)].iter().filter(|&&(r, c)| self.edges[r][c]).count()
+    }
+}
+
+impl GameState for State {
+    type Action = (usize, usize);
+
+    fn status(&self) -> GameStatus {
+        // The game is decided as soon as one player has more than half the boxes
+        let (x, o) = (self.score(Player::X), self.score(Player::O));
+        let remaining = self.n_boxes() - x - o;
+        if x > o + remaining {
+            GameStatus::Win(1)
+        } else if o > x + remaining {
+            GameStatus::Win(-1)
+        } else if remaining == 0 {
+            GameStatus::Draw
+        } else {
+            GameStatus::InProgress
+        }
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.player.value()
+    }
+
+    fn get_legal_actions(&self) -> Vec<Self::Action> {
+        let mut actions = Vec::new();
+
+        for (i, row) in self.edges.iter().enumerate() {
+            for (j, &drawn) in row.iter().enumerate() {
+                if (i + j) % 2 == 1 && !drawn {
+                    actions.push((i, j));
+                }
+            }
+        }
+
+        actions
+    }
+
+    fn get_policy(&self, actions: &[Self::Action]) -> Vec<f64> {
+        // Favour edges that complete a box, and avoid drawing the third side of one, which hands it to the opponent
+        let scores: Vec<f64> = actions.iter().map(|&action| {
+            self.boxes_beside(action).iter().map(|&cell| match self.sides_drawn(cell) {
+                3 => 2.0,
+                2 => -2.0,
+                _ => 0.0,
+            }).sum()
+        }).collect();
+
+        // Return the softmax of the scores
+        let max_score = scores.iter().fold(f64::MIN, |a, &b| a.max(b));
+        let exp_scores: Vec<f64> = scores.iter().map(|s| (s - max_score).exp()).collect();
+        let sum_exp_scores: f64 = exp_scores.iter().sum();
+        exp_scores.iter().map(|s| s / sum_exp_scores).collect()
+    }
+
+    fn get_next_state(&self, action: Self::Action) -> State {
+        let mut next_state = self.clone();
+        next_state.edges[action.0][action.1] = true;
+
+        // Completing a box claims it and earns another turn, so the same player can move several times in a row
+        let mut completed = false;
+        for (row, col) in self.boxes_beside(action) {
+            if next_state.sides_drawn((row, col)) == 4 {
+                next_state.owners[row][col] = Some(self.player);
+                completed = true;
+            }
+        }
+        if !completed {
+            next_state.player = self.player.opponent();
+        }
+        next_state
+    }
+
+    // "h" for a horizontal edge or "v" for a vertical one, then the dot at its left or top end, with columns
+    // from a and rows from 1 at the top. "ha1" is the top edge of the top left box and "va1" its left edge.
+    fn action_to_string(&self, &(row, col): &Self::Action) -> String {
+        if row % 2 == 0 { format!("h{}", square(col / 2, row / 2)) } else { format!("v{}", square(col / 2, row / 2)) }
+    }
+
+    fn parse_action(&self, text: &str) -> Option<Self::Action> {
+        let (dots_down, dots_across) = (self.edges.len() / 2 + 1, self.edges[0].len() / 2 + 1);
+        let action = match text.split_at_checked(1)? {
+            ("h", dot) => parse_square(dot).map(|(col, row)| (2 * row, 2 * col + 1))?,
+            ("v", dot) => parse_square(dot).map(|(col, row)| (2 * row + 1, 2 * col))?,
+            _ => return None,
+        };
+        (action.0 < 2 * dots_down - 1 && action.1 < 2 * dots_across - 1).then_some(action)
+    }
+
+    fn hash_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.edges.hash(&mut hasher);
+        self.owners.hash(&mut hasher);
+        self.player.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+
+#[cfg(feature = "tui")]
+struct BoardRenderer;
+
+#[cfg(feature = "tui")]
+impl Renderer<State> for BoardRenderer {
+    fn render(&self, state: &State, cursor: (usize, usize), hint: Option<(usize, usize)>) {
+        for i in 0..state.edges.len() {
+            for j in 0..state.edges[0].len() {
+                let mut cell = match (i % 2, j % 2) {
+                    (0, 0) => "+".to_string(),
+                    (0, _) => if state.edges[i][j] { "---" } else { "   " }.to_string(),
+                    (_, 0) => if state.edges[i][j] { "|" } else { " " }.to_string(),
+                    _ => format!(" {} ", state.owners[i][j].map_or(' ', Player::symbol)),
+                };
+                if hint == Some((i, j)) {
+                    let mark = if i % 2 == 0 { " * " } else { "*" };
+                    cell = format!("{}{}{}", SetForegroundColor(Color::Yellow), mark, ResetColor);
+                }
+                if (i, j) == cursor {
+                    print!("{}{}{}", SetAttribute(Attribute::Reverse), cell, SetAttribute(Attribute::Reset));
+                } else {
+                    print!("{}", cell);
+                }
+            }
+            println!("\r");
+        }
+        println!("\r");
+        println!("X: {}  O: {}\r", state.score(Player::X), state.score(Player::O));
+    }
+}
+
+// The cursor moves over the whole lattice, so it can be on a dot or a box as well as an edge
+#[cfg(feature = "tui")]
+struct KeyHandler {
+    height: usize,
+    width: usize,
+}
+
+#[cfg(feature = "tui")]
+impl InputHandler<State> for KeyHandler {
+    fn initial_cursor(&self) -> (usize, usize) {
+        (0, 1)
+    }
+
+    fn move_cursor(&self, (row, col): (usize, usize), key: KeyCode) -> Option<(usize, usize)> {
+        match key {
+            KeyCode::Up if row > 0 => Some((row - 1, col)),
+            KeyCode::Down if row < self.height - 1 => Some((row + 1, col)),
+            KeyCode::Left if col > 0 => Some((row, col - 1)),
+            KeyCode::Right if col < self.width - 1 => Some((row, col + 1)),
+            _ => None,
+        }
+    }
+}
+
+
+#[cfg(feature = "tui")]
+pub fn play(rows: usize, cols: usize) -> Result<()> {
+    let options = ui::Options { human: Some(Player::X.value()), ..ui::Options::default() };
+    let input_handler = KeyHandler { height: 2 * rows + 1, width: 2 * cols + 1 };
+    ui::run_game(State::new(rows, cols), options, &BoardRenderer, &input_handler, |root, simulations| Node::best_action(root, simulations.unwrap_or(2000)))
+}
@@ -0,0 +1,190 @@
+use crate::mcts::{GameState, GameStatus};
+use crate::notation::{parse_square, square};
+use crate::player::Player;
+#[cfg(feature = "tui")]
+use crate::mcts::Node;
+#[cfg(feature = "tui")]
+use crate::ui::{self, Renderer, InputHandler};
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "tui")]
+use crossterm::{
+    event::KeyCode,
+    style::{Color, ResetColor, SetForegroundColor},
+    Result,
+};
+
+
+const SIZE: usize = 15;
+const CONNECT: usize = 5;
+const DIRECTIONS: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+#[derive(Clone)]
+pub struct State {
+    board: Vec<Vec<char>>,
+    player: Player,
+    last_move: Option<(usize, usize)>,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
+}
+
+impl State {
+    pub fn new() -> State {
+        State {
+            board: vec![vec![' '; SIZE]; SIZE],
+            player: Player::X,
+            last_move: None,
+        }
+    }
+
+    // Whether a stone of `player` at (row, col) would be part of five in a row
+    fn completes_line(&self, row: usize, col: usize, player: char) -> bool {
+        DIRECTIONS.iter().any(|&(dr, dc)| {
+            let count_from = |sign: isize| {
+                let mut count = 0;
+                let (mut r, mut c) = (row as isize + sign * dr, col as isize + sign * dc);
+                while r >= 0 && r < SIZE as isize && c >= 0 && c < SIZE as isize && self.board[r as usize][c as usize] == player {
+                    count += 1;
+                    r += sign * dr;
+                    c += sign * dc;
+                }
+                count
+            };
+            1 + count_from(1) + count_from(-1) >= CONNECT
+        })
+    }
+}
+
+impl GameState for State {
+    type Action = (usize, usize);
+
+    fn status(&self) -> GameStatus {
+        // Only the last stone placed can have completed a line, for the player who just moved
+        match self.last_move {
+            Some((row, col)) if self.completes_line(row, col, self.board[row][col]) => GameStatus::Win(self.player.opponent().value()),
+            _ if self.board.iter().all(|row| row.iter().all(|&cell| cell != ' ')) => GameStatus::Draw,
+            _ => GameStatus::InProgress,
+        }
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.player.value()
+    }
+
+    fn get_legal_actions(&self) -> Vec<Self::Action> {
+        let mut actions = Vec::new();
+
+        for (i, row) in self.board.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                if cell == ' ' {
+                    actions.push((i, j));
+                }
+            }
+        }
+
+        actions
+    }
+
+    fn get_policy(&self, actions: &[Self::Action]) -> Vec<f64> {
+        let mut scores = Vec::new();
+        for &(row, col) in actions {
+            let mut score = 100.0;
+            if self.completes_line(row, col, self.player.symbol()) { score += 300.0; }
+            if self.completes_line(row, col, self.player.opponent().symbol()) { score += 200.0; }
+            scores.push(score);
+        }
+
+        // Return the softmax of the scores
+        let max_score = scores.iter().fold(f64::MIN, |a, &b| a.max(b));
+        let exp_scores: Vec<f64> = scores.iter().map(|s| (s - max_score).exp()).collect();
+        let sum_exp_scores: f64 = exp_scores.iter().sum();
+        exp_scores.iter().map(|s| s / sum_exp_scores).collect()
+    }
+
+    // Checking every cell for threats is too slow to do on each rollout step over 225 cells
+    fn get_rollout_policy(&self, actions: &[Self::Action]) -> Vec<f64> {
+        vec![1.0 / actions.len() as f64; actions.len()]
+    }
+
+    fn get_next_state(&self, action: Self::Action) -> State {
+        let mut next_board = self.board.clone();
+        next_board[action.0][action.1] = self.player.symbol();
+
+        State {
+            board: next_board,
+            player: self.player.opponent(),
+            last_move: Some(action),
+        }
+    }
+
+    // Columns from a at the left, rows from 1 at the bottom, as in tic-tac-toe
+    fn action_to_string(&self, &(row, col): &Self::Action) -> String {
+        square(col, SIZE - 1 - row)
+    }
+
+    fn parse_action(&self, text: &str) -> Option<Self::Action> {
+        parse_square(text).filter(|&(col, row)| col < SIZE && row < SIZE).map(|(col, row)| (SIZE - 1 - row, col))
+    }
+
+    fn hash_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.board.hash(&mut hasher);
+        self.player.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+
+#[cfg(feature = "tui")]
+struct BoardRenderer;
+
+#[cfg(feature = "tui")]
+impl Renderer<State> for BoardRenderer {
+    fn render(&self, state: &State, cursor: (usize, usize), hint: Option<(usize, usize)>) {
+        let separator = format!("+{}\r", "---+".repeat(SIZE));
+        println!("{}", separator);
+        for i in 0..SIZE {
+            for j in 0..SIZE {
+                let mut cell = state.board[i][j].to_string();
+                if hint == Some((i, j)) {
+                    cell = format!("{}*{}", SetForegroundColor(Color::Yellow), ResetColor);
+                }
+                let marker = if (i, j) == cursor { format!("[{}]", cell) } else { format!(" {} ", cell) };
+                print!("|{}", marker);
+            }
+            println!("|\r");
+            println!("{}", separator);
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+struct KeyHandler;
+
+#[cfg(feature = "tui")]
+impl InputHandler<State> for KeyHandler {
+    fn initial_cursor(&self) -> (usize, usize) {
+        (SIZE / 2, SIZE / 2)
+    }
+
+    fn move_cursor(&self, (row, col): (usize, usize), key: KeyCode) -> Option<(usize, usize)> {
+        match key {
+            KeyCode::Up if row > 0 => Some((row - 1, col)),
+            KeyCode::Down if row < SIZE - 1 => Some((row + 1, col)),
+            KeyCode::Left if col > 0 => Some((row, col - 1)),
+            KeyCode::Right if col < SIZE - 1 => Some((row, col + 1)),
+            _ => None,
+        }
+    }
+}
+
+
+#[cfg(feature = "tui")]
+pub fn play() -> Result<()> {
+    let options = ui::Options { human: Some(Player::X.value()), ..ui::Options::default() };
+    ui::run_game(State::new(), options, &BoardRenderer, &KeyHandler, |root, simulations| Node::best_action(root, simulations.unwrap_or(1000)))
+}
@@ -0,0 +1,24 @@
+pub mod mcts;
+mod notation;
+pub mod player;
+pub mod arena;
+pub mod agent;
+pub mod searcher;
+#[cfg(feature = "sync")]
+pub mod shared;
+pub mod ttt;
+pub mod connect4;
+pub mod othello;
+pub mod gomoku;
+pub mod nim;
+pub mod dots_and_boxes;
+pub mod mancala;
+pub mod tournament;
+#[cfg(feature = "tui")]
+pub mod ui;
+#[cfg(feature = "serde")]
+pub mod save;
+#[cfg(feature = "testing")]
+pub mod consistency;
+#[cfg(feature = "testing")]
+pub use consistency::verify_game_consistency;
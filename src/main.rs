@@ -1,14 +1,220 @@
-mod mcts;
-mod ttt;
-mod connect4;
+use mcts::{ttt, connect4, othello, gomoku, nim, dots_and_boxes, mancala, tournament, ui};
+use mcts::mcts::SearchConfig;
 use std::env;
 use crossterm::Result;
 
-fn main() -> Result<()> {
+fn invalid_input(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+// Maps `--level 1|2|3` to a simulation count for the AI
+fn parse_level(args: &[String]) -> Result<Option<i32>> {
+    match args.iter().position(|arg| arg == "--level") {
+        None => Ok(None),
+        Some(i) => match args.get(i + 1).map(String::as_str) {
+            Some("1") => Ok(Some(100)),
+            Some("2") => Ok(Some(1000)),
+            Some("3") => Ok(Some(10000)),
+            _ => Err(invalid_input("--level must be followed by 1, 2 or 3")),
+        },
+    }
+}
+
+// Reads the saved game path from `--load <path>`, which needs the serde feature
+fn parse_load(args: &[String]) -> Result<Option<&str>> {
+    match args.iter().position(|arg| arg == "--load") {
+        None => Ok(None),
+        Some(_) if !cfg!(feature = "serde") => Err(invalid_input("--load requires building with --features serde")),
+        Some(i) => args.get(i + 1).map(|path| Some(path.as_str())).ok_or_else(|| invalid_input("--load must be followed by a path")),
+    }
+}
+
+// Reads the starting position file from `--position <path>`, which sets the board size itself
+fn parse_position(args: &[String]) -> Result<Option<&str>> {
+    match args.iter().position(|arg| arg == "--position") {
+        None => Ok(None),
+        Some(_) if args.iter().any(|arg| arg == "--board" || arg == "--load") => Err(invalid_input("--position can't be combined with --board or --load")),
+        Some(i) => args.get(i + 1).map(|path| Some(path.as_str())).ok_or_else(|| invalid_input("--position must be followed by a path")),
+    }
+}
+
+// Plays connect4 games between two simulation counts: `bench <games> <sims_a> <sims_b> [seed]`
+fn bench(args: &[String]) -> Result<()> {
+    let parse = |i: usize, default: u64| args.get(i).map_or(Ok(default), |arg| arg.parse::<u64>())
+        .map_err(|_| invalid_input("bench arguments must be non-negative integers"));
+    let n_games = parse(0, 20)? as usize;
+    let (sims_a, sims_b) = (parse(1, 500)? as i32, parse(2, 2000)? as i32);
+    let seed = parse(3, 0)?;
+
+    let config_a = SearchConfig { n_simulations: sims_a, ..SearchConfig::default() };
+    let config_b = SearchConfig { n_simulations: sims_b, ..SearchConfig::default() };
+    let tally = tournament::run_match(&connect4::State::new(), n_games, &config_a, &config_b, seed);
+    println!("{} vs {} simulations over {} games (seed {}): {} wins, {} losses, {} draws",
+        sims_a, sims_b, n_games, seed, tally.wins, tally.losses, tally.draws);
+    Ok(())
+}
+
+// Reads the connect4 geometry from `--board <rows>x<cols>` and `--connect <n>`, defaulting to 6x7 and four in a row
+fn parse_board(args: &[String]) -> Result<(usize, usize, usize)> {
+    let value_of = |flag: &str| args.iter().position(|arg| arg == flag).map(|i| args.get(i + 1).map(String::as_str));
+    let (rows, cols) = match value_of("--board") {
+        None => (6, 7),
+        Some(value) => value.and_then(|value| value.split_once('x'))
+            .and_then(|(rows, cols)| Some((rows.parse().ok()?, cols.parse().ok()?)))
+            .filter(|&(rows, cols)| rows > 0 && cols > 0)
+            .ok_or_else(|| invalid_input("--board must be followed by <rows>x<cols>, e.g. 8x9"))?,
+    };
+    let connect_len = match value_of("--connect") {
+        None => 4,
+        Some(value) => value.and_then(|value| value.parse().ok())
+            .filter(|&connect_len| connect_len > 0)
+            .ok_or_else(|| invalid_input("--connect must be followed by a positive line length"))?,
+    };
+    Ok((rows, cols, connect_len))
+}
+
+// The side the human plays: first by default, second with `--ai-first`, or neither with `--watch`
+fn parse_human(args: &[String]) -> Option<i32> {
+    if args.iter().any(|arg| arg == "--watch") {
+        None
+    } else if args.iter().any(|arg| arg == "--ai-first") {
+        Some(-1)
+    } else {
+        Some(1)
+    }
+}
+
+fn parse_options(args: &[String]) -> ui::Options {
+    ui::Options {
+        human: parse_human(args),
+        ponder: args.iter().any(|arg| arg == "--ponder"),
+        verbose: args.iter().any(|arg| arg == "--verbose"),
+        simulations: None,  // Filled in from --level by each game's play()
+    }
+}
+
+// The players' marks from `--symbols <first><second>`, e.g. `--symbols ●○`, if given
+fn parse_symbols(args: &[String]) -> Result<Option<[char; 2]>> {
+    match args.iter().position(|arg| arg == "--symbols") {
+        None => Ok(None),
+        Some(index) => {
+            let symbols: Vec<char> = args.get(index + 1).map(|value| value.chars().collect()).unwrap_or_default();
+            match symbols[..] {
+                [first, second] if first != second && first != ' ' && second != ' ' => Ok(Some([first, second])),
+                _ => Err(invalid_input("--symbols must be followed by two different, non-blank characters")),
+            }
+        },
+    }
+}
+
+// The flags ttt and connect4 take, with a description for --help
+const PLAY_FLAGS: &[(&str, &str)] = &[
+    ("--level <1|2|3>", "AI strength: 100, 1000 or 10000 simulations per move"),
+    ("--ai-first", "let the AI open the game"),
+    ("--watch", "have the AI play both sides"),
+    ("--ponder", "let the AI keep searching while you think"),
+    ("--verbose", "list the AI's move statistics after it plays"),
+    ("--symbols <ab>", "draw the players with the marks a and b"),
+    ("--load <path>", "resume a saved game (needs --features serde)"),
+];
+
+const CONNECT4_FLAGS: &[(&str, &str)] = &[
+    ("--board <rows>x<cols>", "play on another board size, 6x7 by default"),
+    ("--connect <n>", "the line length needed to win, 4 by default"),
+    ("--position <file>", "start from the board in a file, one row per line with . for empty cells"),
+];
+
+struct Command {
+    name: &'static str,
+    args: &'static str,
+    about: &'static str,
+    flags: &'static [&'static [(&'static str, &'static str)]],
+    run: fn(&[String]) -> Result<()>,
+}
+
+const COMMANDS: &[Command] = &[
+    Command { name: "ttt", args: "[flags]", about: "tic-tac-toe", flags: &[PLAY_FLAGS], run: |args| {
+        ttt::play(parse_level(args)?, parse_options(args), parse_symbols(args)?, parse_load(args)?)
+    }},
+    Command { name: "connect4", args: "[flags]", about: "connect four", flags: &[PLAY_FLAGS, CONNECT4_FLAGS], run: |args| {
+        connect4::play(parse_level(args)?, parse_options(args), parse_symbols(args)?, parse_load(args)?, parse_position(args)?, parse_board(args)?)
+    }},
+    Command { name: "othello", args: "", about: "othello", flags: &[], run: |_| othello::play() },
+    Command { name: "gomoku", args: "", about: "gomoku", flags: &[], run: |_| gomoku::play() },
+    Command { name: "nim", args: "[pile sizes...]", about: "nim, with piles of 3, 4 and 5 by default", flags: &[], run: |args| {
+        let piles = args.iter().map(|arg| arg.parse::<usize>()).collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| invalid_input("Invalid pile size"))?;
+        nim::play(if piles.is_empty() { nim::DEFAULT_PILES.to_vec() } else { piles })
+    }},
+    Command { name: "dots", args: "[rows cols]", about: "dots and boxes, on 3x3 boxes by default", flags: &[], run: |args| {
+        let size = args.iter().map(|arg| arg.parse::<usize>()).collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| invalid_input("Invalid board size"))?;
+        match size[..] {
+            [] => dots_and_boxes::play(dots_and_boxes::DEFAULT_SIZE.0, dots_and_boxes::DEFAULT_SIZE.1),
+            [rows, cols] if rows > 0 && cols > 0 => dots_and_boxes::play(rows, cols),
+            _ => Err(invalid_input("dots takes the number of rows and columns of boxes")),
+        }
+    }},
+    Command { name: "mancala", args: "", about: "kalah, with six pits a side", flags: &[], run: |_| mancala::play() },
+    Command { name: "bench", args: "[games] [sims_a] [sims_b] [seed]", about: "play connect4 games between two simulation counts", flags: &[], run: bench },
+];
+
+fn usage() -> String {
+    let mut usage = String::from("Usage: connect4 <game> [args]\n\nGames:\n");
+    for command in COMMANDS {
+        usage += &format!("  {:<10} {}\n", command.name, command.about);
+    }
+    usage + "\nRun `connect4 <game> --help` for the arguments a game takes."
+}
+
+fn command_usage(command: &Command) -> String {
+    let mut usage = format!("Usage: connect4 {} {}", command.name, command.args);
+    let flags: Vec<&(&str, &str)> = command.flags.iter().flat_map(|flags| flags.iter()).collect();
+    if !flags.is_empty() {
+        usage += "\n\nFlags:";
+        for (flag, about) in flags {
+            usage += &format!("\n  {:<24} {}", flag, about);
+        }
+    }
+    usage
+}
+
+// Rejects flags the command doesn't take, which would otherwise be silently ignored
+fn check_flags(command: &Command, args: &[String]) -> Result<()> {
+    let known = |arg: &str| command.flags.iter().flat_map(|flags| flags.iter()).any(|(flag, _)| flag.split(' ').next() == Some(arg));
+    match args.iter().find(|arg| arg.starts_with("--") && !known(arg)) {
+        Some(arg) => Err(invalid_input(&format!("unknown flag {}", arg))),
+        None => Ok(()),
+    }
+}
+
+fn main() {
     let args: Vec<String> = env::args().collect();
-    match args.get(1).map(String::as_str) {
-        Some("ttt") => ttt::play(),
-        Some("connect4") => connect4::play(),
-        _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid game selection").into()),
+    let Some(name) = args.get(1).map(String::as_str) else {
+        eprintln!("{}", usage());
+        std::process::exit(2);
+    };
+    if matches!(name, "--help" | "-h" | "help") {
+        println!("{}", usage());
+        return;
+    }
+    let Some(command) = COMMANDS.iter().find(|command| command.name == name) else {
+        eprintln!("error: unknown game {}\n\n{}", name, usage());
+        std::process::exit(2);
+    };
+
+    let args = &args[2..];
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", command_usage(command));
+        return;
+    }
+    if let Err(err) = check_flags(command, args).and_then(|_| (command.run)(args)) {
+        // Bad arguments get the usage too, unlike errors from the terminal
+        if err.kind() == std::io::ErrorKind::InvalidInput {
+            eprintln!("error: {}\n\n{}", err, command_usage(command));
+            std::process::exit(2);
+        }
+        eprintln!("error: {}", err);
+        std::process::exit(1);
     }
 }
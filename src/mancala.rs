@@ -0,0 +1,233 @@
+use crate::mcts::{GameState, GameStatus};
+use crate::notation::parse_number;
+use crate::player::Player;
+#[cfg(feature = "tui")]
+use crate::mcts::Node;
+#[cfg(feature = "tui")]
+use crate::ui::{self, Renderer, InputHandler};
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "tui")]
+use crossterm::{
+    event::KeyCode,
+    style::{Color, ResetColor, SetForegroundColor},
+    Result,
+};
+
+
+const PITS: usize = 6;
+const SEEDS: u32 = 4;
+// Sowing goes counterclockwise through X's pits, X's store, O's pits and O's store
+const STORES: [usize; 2] = [PITS, 2 * PITS + 1];
+
+// Kalah with six pits a side. An action is the pit to sow from, counted from 0 at the mover's left.
+#[derive(Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct State {
+    board: [u32; 2 * PITS + 2],
+    player: Player,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
+}
+
+impl State {
+    pub fn new() -> State {
+        let mut board = [SEEDS; 2 * PITS + 2];
+        board[STORES[0]] = 0;
+        board[STORES[1]] = 0;
+        State { board, player: Player::X }
+    }
+
+    // Seeds in `player`'s store
+    pub fn store(&self, player: Player) -> u32 {
+        self.board[STORES[player.index()]]
+    }
+
+    // Seeds in `player`'s pits, from their left
+    pub fn pits(&self, player: Player) -> &[u32] {
+        let start = player.index() * (PITS + 1);
+        &self.board[start..start + PITS]
+    }
+
+    fn side_empty(&self, side: usize) -> bool {
+        self.board[side * (PITS + 1)..side * (PITS + 1) + PITS].iter().all(|&seeds| seeds == 0)
+    }
+}
+
+impl GameState for State {
+    type Action = usize;
+
+    fn status(&self) -> GameStatus {
+        // get_next_state() sweeps the board into the stores once either side runs out
+        if !self.side_empty(0) && !self.side_empty(1) {
+            return GameStatus::InProgress;
+        }
+        let (x, o) = (self.store(Player::X), self.store(Player::O));
+        if x > o {
+            GameStatus::Win(1)
+        } else if o > x {
+            GameStatus::Win(-1)
+        } else {
+            GameStatus::Draw
+        }
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.player.value()
+    }
+
+    fn get_legal_actions(&self) -> Vec<Self::Action> {
+        (0..PITS).filter(|&pit| self.pits(self.player)[pit] > 0).collect()
+    }
+
+    fn get_policy(&self, actions: &[Self::Action]) -> Vec<f64> {
+        // Favour moves that earn another turn or add to the store, e.g. with a capture
+        let scores: Vec<f64> = actions.iter().map(|&action| {
+            let next_state = self.get_next_state(action);
+            let gain = next_state.store(self.player) - self.store(self.player);
+            let extra_turn = next_state.player == self.player && !next_state.is_terminal();
+            gain as f64 * 0.5 + if extra_turn { 1.0 } else { 0.0 }
+        }).collect();
+
+        // Return the softmax of the scores
+        let max_score = scores.iter().fold(f64::MIN, |a, &b| a.max(b));
+        let exp_scores: Vec<f64> = scores.iter().map(|s| (s - max_score).exp()).collect();
+        let sum_exp_scores: f64 = exp_scores.iter().sum();
+        exp_scores.iter().map(|s| s / sum_exp_scores).collect()
+    }
+
+    fn get_next_state(&self, action: Self::Action) -> State {
+        let mut board = self.board;
+        let own = self.player.index();
+        let (own_store, opponent_store) = (STORES[own], STORES[1 - own]);
+
+        // Sow one seed into each following pit, skipping the opponent's store
+        let mut index = own * (PITS + 1) + action;
+        let mut seeds = std::mem::take(&mut board[index]);
+        while seeds > 0 {
+            index = (index + 1) % board.len();
+            if index != opponent_store {
+                board[index] += 1;
+                seeds -= 1;
+            }
+        }
+
+        // The last seed landing in one of your own empty pits captures it along with the pit opposite
+        let on_own_side = index >= own * (PITS + 1) && index < own * (PITS + 1) + PITS;
+        if on_own_side && board[index] == 1 && board[2 * PITS - index] > 0 {
+            let opposite = 2 * PITS - index;
+            board[own_store] += board[index] + board[opposite];
+            board[index] = 0;
+            board[opposite] = 0;
+        }
+
+        // Ending in your own store earns another turn
+        let player = if index == own_store { self.player } else { self.player.opponent() };
+        let mut next_state = State { board, player };
+
+        // Once either side is empty, each player keeps the seeds left on their own side
+        if next_state.side_empty(0) || next_state.side_empty(1) {
+            for (side, &store) in STORES.iter().enumerate() {
+                for pit in side * (PITS + 1)..side * (PITS + 1) + PITS {
+                    next_state.board[store] += std::mem::take(&mut next_state.board[pit]);
+                }
+            }
+        }
+        next_state
+    }
+
+    // The pit number from 1 at the mover's left, e.g. "3"
+    fn action_to_string(&self, pit: &Self::Action) -> String {
+        (pit + 1).to_string()
+    }
+
+    fn parse_action(&self, text: &str) -> Option<Self::Action> {
+        let number = parse_number(text)?;
+        (1..=PITS).contains(&number).then(|| number - 1)
+    }
+
+    fn hash_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+
+#[cfg(feature = "tui")]
+struct BoardRenderer;
+
+#[cfg(feature = "tui")]
+impl Renderer<State> for BoardRenderer {
+    // O's pits run right to left along the top, X's left to right along the bottom
+    fn render(&self, state: &State, cursor: usize, hint: Option<usize>) {
+        let row = |pits: Vec<u32>| pits.iter().map(|seeds| format!("[{:>2}]", seeds)).collect::<String>();
+        println!("     {}\r", row(state.pits(Player::O).iter().rev().copied().collect()));
+        println!("[{:>2}]{}[{:>2}]\r", state.store(Player::O), " ".repeat(4 * PITS + 2), state.store(Player::X));
+        println!("     {}\r", row(state.pits(Player::X).to_vec()));
+        let markers: String = (0..PITS).map(|pit| match (pit == cursor, hint == Some(pit)) {
+            (_, true) => format!("  {}*{} ", SetForegroundColor(Color::Yellow), ResetColor),
+            (true, false) => "  ^ ".to_string(),
+            (false, false) => "    ".to_string(),
+        }).collect();
+        println!("     {}\r", markers.trim_end());
+    }
+}
+
+#[cfg(feature = "tui")]
+struct KeyHandler;
+
+#[cfg(feature = "tui")]
+impl InputHandler<State> for KeyHandler {
+    fn initial_cursor(&self) -> usize {
+        0
+    }
+
+    fn move_cursor(&self, pit: usize, key: KeyCode) -> Option<usize> {
+        match key {
+            KeyCode::Left if pit > 0 => Some(pit - 1),
+            KeyCode::Right if pit < PITS - 1 => Some(pit + 1),
+            _ => None,
+        }
+    }
+}
+
+
+#[cfg(feature = "tui")]
+pub fn play() -> Result<()> {
+    let options = ui::Options { human: Some(Player::X.value()), ..ui::Options::default() };
+    ui::run_game(State::new(), options, &BoardRenderer, &KeyHandler, |root, simulations| Node::best_action(root, simulations.unwrap_or(2000)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn landing_in_an_empty_pit_captures_the_pit_opposite() {
+        // X's single seed in pit 1 lands in empty pit 2, across from the five seeds in O's pit 3
+        let mut board = [0; 2 * PITS + 2];
+        board[1] = 1;
+        board[5] = 1;
+        board[PITS + 1 + 3] = 5;
+        board[PITS + 1 + 5] = 1;
+        let state = State { board, player: Player::X };
+
+        let next_state = state.get_next_state(1);
+        assert_eq!(next_state.store(Player::X), 6);
+        assert_eq!(next_state.pits(Player::X), &[0, 0, 0, 0, 0, 1]);
+        assert_eq!(next_state.pits(Player::O), &[0, 0, 0, 0, 0, 1]);
+        assert_eq!(next_state.get_player_turn(), Player::O.value());
+
+        // Ending in the store instead earns another turn, without a capture
+        let next_state = state.get_next_state(5);
+        assert_eq!(next_state.store(Player::X), 1);
+        assert_eq!(next_state.pits(Player::O), &[0, 0, 0, 5, 0, 1]);
+        assert_eq!(next_state.get_player_turn(), Player::X.value());
+    }
+}
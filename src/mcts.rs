@@ -1,20 +1,786 @@
-use std::fmt::Debug;
+use std::cmp::Ordering;
+use std::fmt::{self, Debug, Write};
 use std::rc::{Rc, Weak};
 use std::cell::RefCell;
 use std::hash::Hash;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::thread;
 use rand::distributions::WeightedIndex;
 use rand::prelude::*;
+use rand_distr::Dirichlet;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStatus {
+    InProgress,
+    Draw,
+    // Holds the get_player_turn() of the winner
+    Win(i32),
+}
 
 pub trait GameState {
     type Action: Eq + Hash + Copy + Debug;
 
-    fn is_terminal(&self) -> bool;
+    fn status(&self) -> GameStatus;
     fn get_player_turn(&self) -> i32;
     fn get_legal_actions(&self) -> Vec<Self::Action>;
-    fn get_policy(&self, actions: &Vec<Self::Action>) -> Vec<f64>;
+    fn get_policy(&self, actions: &[Self::Action]) -> Vec<f64>;
     fn get_next_state(&self, action: Self::Action) -> Self;
-    fn get_result(&self) -> i32;
+    fn hash_key(&self) -> u64;
+
+    // Key shared by positions that are rotations or reflections of each other, so the transposition table pools
+    // their statistics. Games without symmetries can leave it as hash_key().
+    fn canonical_key(&self) -> u64 {
+        self.hash_key()
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.status() != GameStatus::InProgress
+    }
+
+    // A move's name in the game's notation, e.g. "b2" in tic-tac-toe, for logs and replays. Defaults to its Debug form.
+    fn action_to_string(&self, action: &Self::Action) -> String {
+        format!("{:?}", action)
+    }
+
+    // The move `text` names in action_to_string()'s notation, or None if it doesn't name one. Named moves aren't
+    // necessarily legal.
+    fn parse_action(&self, _text: &str) -> Option<Self::Action> {
+        None
+    }
+
+    // Whether the next step is down to chance rather than a player's choice, e.g. a dice roll. The search then
+    // samples one of get_chance_outcomes() instead of choosing an action.
+    fn is_chance_node(&self) -> bool {
+        false
+    }
+
+    // The states a chance node can lead to, each with its probability
+    fn get_chance_outcomes(&self) -> Vec<(Self, f64)> where Self: Sized {
+        Vec::new()
+    }
+
+    // Value in [-1, 1] from the perspective of the player whose get_player_turn() is 1, by default 1 for a win
+    // and 0 for a draw or an unfinished game. Games scored by margin can override it with something finer.
+    fn get_result(&self) -> f64 {
+        match self.status() {
+            GameStatus::Win(player) => if player == 1 { 1. } else { -1. },
+            GameStatus::Draw | GameStatus::InProgress => 0.,
+        }
+    }
+
+    // Weights used to sample moves during rollouts, defaulting to the tree search prior
+    fn get_rollout_policy(&self, actions: &[Self::Action]) -> Vec<f64> {
+        self.get_policy(actions)
+    }
+
+    // Heuristic estimate of a non-terminal state in the same perspective as get_result(), used when rollouts are cut off
+    fn evaluate(&self) -> f64 {
+        0.
+    }
+
+    fn num_players(&self) -> usize {
+        2
+    }
+
+    // Index in 0..num_players() of the player to move, by default derived from the two-player get_player_turn()
+    fn get_player_index(&self) -> usize {
+        if self.get_player_turn() == 1 { 0 } else { 1 }
+    }
+
+    // Number of moves played to reach this state, for games that track it. Needed for SearchConfig::opening_moves.
+    fn move_count(&self) -> Option<usize> {
+        None
+    }
+
+    // Per-player values indexed like get_player_index(), by default the zero-sum split of get_result()
+    fn get_results(&self) -> Vec<f64> {
+        let result = self.get_result();
+        vec![result, -result]
+    }
+
+    // Per-player heuristic estimates, by default the zero-sum split of evaluate()
+    fn evaluate_results(&self) -> Vec<f64> {
+        let value = self.evaluate();
+        vec![value, -value]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionPolicy {
+    // Classic UCB1, for games without a meaningful prior from get_policy
+    Ucb1 { c: f64 },
+    // AlphaZero-style PUCT, weighting exploration by the prior
+    Puct,
+}
+
+// An evaluator such as a neural network that scores leaves in place of a rollout and supplies the priors for
+// their children, AlphaZero-style. See Node::best_action_with_provider().
+pub trait ValuePolicyProvider<State: GameState> {
+    // A value in [-1, 1] in the same perspective as get_result(), and a prior for each legal action
+    fn evaluate(&self, state: &State) -> (f64, Vec<(State::Action, f64)>);
+}
+
+// How the move is chosen once the search is done, when the temperature is 0
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FinalSelection {
+    // The most visited child, the usual robust choice
+    MaxVisits,
+    // The child with the best mean value among those with at least `min_visits`, falling back to MaxVisits if none has
+    MaxValue { min_visits: i32 },
+}
+
+// How moves are picked during a rollout, given the weights from get_rollout_policy()
+pub trait RolloutStrategy: Debug + Send + Sync {
+    // Index of the action to play next, or None to stop and score the position with evaluate_results()
+    fn choose(&self, weights: &[f64], rng: &mut dyn RngCore) -> Option<usize>;
+}
+
+// Samples moves in proportion to their weights, falling back to a uniform choice if the weights are unusable (e.g. all zero)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomRollout;
+
+impl RolloutStrategy for RandomRollout {
+    fn choose(&self, weights: &[f64], rng: &mut dyn RngCore) -> Option<usize> {
+        match WeightedIndex::new(weights) {
+            Ok(dist) => Some(dist.sample(rng)),
+            Err(_) => Some(rng.gen_range(0..weights.len())),
+        }
+    }
+}
+
+// Always plays the highest-weighted move, taking the first on ties
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GreedyRollout;
+
+impl RolloutStrategy for GreedyRollout {
+    fn choose(&self, weights: &[f64], _rng: &mut dyn RngCore) -> Option<usize> {
+        (0..weights.len()).rev().max_by(|&a, &b| compare_scores(weights[a], weights[b]))
+    }
+}
+
+// Skips the playout and scores leaves with the heuristic evaluation straight away
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoRollout;
+
+impl RolloutStrategy for NoRollout {
+    fn choose(&self, _weights: &[f64], _rng: &mut dyn RngCore) -> Option<usize> {
+        None
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchConfig {
+    pub pb_c_init: f64,
+    pub pb_c_base: f64,
+    pub n_simulations: i32,
+    pub temperature: f64,
+    pub selection_policy: SelectionPolicy,
+    pub max_rollout_depth: Option<usize>,
+    // Playouts still going after this many moves are scored as a draw, so games that can cycle always finish.
+    // Unlike max_rollout_depth this isn't meant to cut playouts short, and the games here never come near it.
+    pub max_rollout_moves: usize,
+    // Rollouts stop as soon as evaluate_results() gives some player a value at least this far from 0, and score
+    // the position with it. Saves playing out lines that are already decided, at the cost of an evaluation per move.
+    pub rollout_confidence_cutoff: Option<f64>,
+    // Value in [-1, 1] assumed for unvisited children when scoring them with PUCT, from the selecting player's perspective
+    pub first_play_urgency: f64,
+    // Skip the search when the outcome is already decided one move ahead, see decided_action()
+    pub quick_win: bool,
+    pub rollout_strategy: Arc<dyn RolloutStrategy>,
+    // Independent playouts averaged for each leaf, which still counts as a single visit
+    pub rollouts_per_leaf: usize,
+    // For this many moves into the game, the final move is sampled in proportion to visits for variety, as if the
+    // temperature were 1. Only applies to games whose move_count() is known.
+    pub opening_moves: usize,
+    pub final_selection: FinalSelection,
+    // Leave out children that lose on the spot or hand the opponent a one-move win, when a safer move exists.
+    // Costs a scan of the replies at every expansion, but stops weak searches from walking into simple tactics.
+    pub prune_losing_moves: bool,
+    // Once the tree grows past this many nodes, the least-visited subtrees are dropped until it's back down to
+    // three quarters of the budget. Their roots stay in the tree with their statistics, and are expanded again
+    // if the search comes back to them.
+    pub max_nodes: Option<usize>,
+    // Chance in [0, 1] that the final move is picked uniformly from all the legal moves instead, for a weaker
+    // opponent whose mistakes look more human than those of a short search
+    pub blunder_rate: f64,
+    // Added to the second player's value of every leaf and taken off the first's, like komi in go, to offset the
+    // advantage of moving first. Only applies to two-player games, and can push values past [-1, 1].
+    pub komi: f64,
+    // Add a node's children one at a time, highest prior first, with each visit to it creating the next, instead of
+    // all of them on its first visit. Saves memory in games with many moves, like gomoku, where most children of a
+    // node would never be searched.
+    pub expand_one: bool,
+    // Play the exact best move from endgame_action() instead of searching, when every line it needs to look at
+    // ends within this many moves. Connect4 gets there with this many empty cells left.
+    pub endgame_exact_depth: Option<usize>,
+    // Weight of a progressive bias added to every child's selection score: this times its prior from get_policy(),
+    // over its visits + 1. Leans on the heuristic while a node is new and fades as the visits come in, on top of
+    // the prior's part in PUCT, and gives UCB1 a use for the prior at all.
+    pub progressive_bias: f64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            pb_c_init: 1.25,
+            pb_c_base: 19652.,
+            n_simulations: 1000,
+            temperature: 0.,
+            selection_policy: SelectionPolicy::Puct,
+            max_rollout_depth: None,
+            max_rollout_moves: 10_000,
+            rollout_confidence_cutoff: None,
+            first_play_urgency: 1.,
+            quick_win: false,
+            rollout_strategy: Arc::new(RandomRollout),
+            rollouts_per_leaf: 1,
+            opening_moves: 0,
+            final_selection: FinalSelection::MaxVisits,
+            prune_losing_moves: false,
+            max_nodes: None,
+            blunder_rate: 0.,
+            komi: 0.,
+            expand_one: false,
+            endgame_exact_depth: None,
+            progressive_bias: 0.,
+        }
+    }
+}
+
+// Why SearchConfigBuilder::build() rejected a config
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid search config: {}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl SearchConfig {
+    // Starts from the defaults, e.g. SearchConfig::builder().n_simulations(5000).temperature(0.5).build()
+    pub fn builder() -> SearchConfigBuilder {
+        SearchConfigBuilder { config: SearchConfig::default() }
+    }
+}
+
+// Sets SearchConfig fields one at a time, checking them together in build()
+#[derive(Clone, Debug)]
+pub struct SearchConfigBuilder {
+    config: SearchConfig,
+}
+
+impl SearchConfigBuilder {
+    pub fn pb_c_init(mut self, pb_c_init: f64) -> Self {
+        self.config.pb_c_init = pb_c_init;
+        self
+    }
+
+    pub fn pb_c_base(mut self, pb_c_base: f64) -> Self {
+        self.config.pb_c_base = pb_c_base;
+        self
+    }
+
+    pub fn n_simulations(mut self, n_simulations: i32) -> Self {
+        self.config.n_simulations = n_simulations;
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.config.temperature = temperature;
+        self
+    }
+
+    pub fn selection_policy(mut self, selection_policy: SelectionPolicy) -> Self {
+        self.config.selection_policy = selection_policy;
+        self
+    }
+
+    pub fn max_rollout_depth(mut self, max_rollout_depth: usize) -> Self {
+        self.config.max_rollout_depth = Some(max_rollout_depth);
+        self
+    }
+
+    pub fn max_rollout_moves(mut self, max_rollout_moves: usize) -> Self {
+        self.config.max_rollout_moves = max_rollout_moves;
+        self
+    }
+
+    pub fn rollout_confidence_cutoff(mut self, rollout_confidence_cutoff: f64) -> Self {
+        self.config.rollout_confidence_cutoff = Some(rollout_confidence_cutoff);
+        self
+    }
+
+    pub fn first_play_urgency(mut self, first_play_urgency: f64) -> Self {
+        self.config.first_play_urgency = first_play_urgency;
+        self
+    }
+
+    pub fn quick_win(mut self, quick_win: bool) -> Self {
+        self.config.quick_win = quick_win;
+        self
+    }
+
+    pub fn rollout_strategy(mut self, rollout_strategy: impl RolloutStrategy + 'static) -> Self {
+        self.config.rollout_strategy = Arc::new(rollout_strategy);
+        self
+    }
+
+    pub fn rollouts_per_leaf(mut self, rollouts_per_leaf: usize) -> Self {
+        self.config.rollouts_per_leaf = rollouts_per_leaf;
+        self
+    }
+
+    pub fn opening_moves(mut self, opening_moves: usize) -> Self {
+        self.config.opening_moves = opening_moves;
+        self
+    }
+
+    pub fn final_selection(mut self, final_selection: FinalSelection) -> Self {
+        self.config.final_selection = final_selection;
+        self
+    }
+
+    pub fn prune_losing_moves(mut self, prune_losing_moves: bool) -> Self {
+        self.config.prune_losing_moves = prune_losing_moves;
+        self
+    }
+
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.config.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub fn blunder_rate(mut self, blunder_rate: f64) -> Self {
+        self.config.blunder_rate = blunder_rate;
+        self
+    }
+
+    pub fn komi(mut self, komi: f64) -> Self {
+        self.config.komi = komi;
+        self
+    }
+
+    pub fn expand_one(mut self, expand_one: bool) -> Self {
+        self.config.expand_one = expand_one;
+        self
+    }
+
+    pub fn endgame_exact_depth(mut self, endgame_exact_depth: usize) -> Self {
+        self.config.endgame_exact_depth = Some(endgame_exact_depth);
+        self
+    }
+
+    pub fn progressive_bias(mut self, progressive_bias: f64) -> Self {
+        self.config.progressive_bias = progressive_bias;
+        self
+    }
+
+    pub fn build(self) -> Result<SearchConfig, ConfigError> {
+        let config = self.config;
+        let check = |ok: bool, message: &str| if ok { Ok(()) } else { Err(ConfigError(message.to_string())) };
+        // Comparisons with NaN are false, so NaN fails each check
+        check(config.pb_c_init >= 0. && config.pb_c_init.is_finite(), "pb_c_init must be a finite number >= 0")?;
+        check(config.pb_c_base > 0. && config.pb_c_base.is_finite(), "pb_c_base must be a finite number > 0")?;
+        check(config.n_simulations > 0, "n_simulations must be > 0")?;
+        check(config.temperature >= 0. && config.temperature.is_finite(), "temperature must be a finite number >= 0")?;
+        if let SelectionPolicy::Ucb1 { c } = config.selection_policy {
+            check(c >= 0. && c.is_finite(), "the UCB1 exploration constant must be a finite number >= 0")?;
+        }
+        check(config.max_rollout_moves > 0, "max_rollout_moves must be > 0")?;
+        if let Some(cutoff) = config.rollout_confidence_cutoff {
+            check(cutoff > 0. && cutoff <= 1., "rollout_confidence_cutoff must be in (0, 1]")?;
+        }
+        check((-1. ..=1.).contains(&config.first_play_urgency), "first_play_urgency must be in [-1, 1]")?;
+        check(config.rollouts_per_leaf > 0, "rollouts_per_leaf must be > 0")?;
+        check(config.max_nodes != Some(0), "max_nodes must be > 0")?;
+        check((0. ..=1.).contains(&config.blunder_rate), "blunder_rate must be in [0, 1]")?;
+        check(config.komi.is_finite(), "komi must be a finite number")?;
+        check(config.progressive_bias >= 0. && config.progressive_bias.is_finite(), "progressive_bias must be a finite number >= 0")?;
+        Ok(config)
+    }
+}
+
+#[derive(Default)]
+struct TableEntry {
+    visit_count: i32,
+    total_values: Vec<f64>,
+}
+
+#[derive(Default)]
+pub struct TranspositionTable {
+    entries: HashMap<u64, TableEntry>,
+}
+
+impl TranspositionTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Visits recorded under a state's canonical_key()
+    pub fn visit_count(&self, key: u64) -> i32 {
+        self.entries.get(&key).map_or(0, |entry| entry.visit_count)
+    }
+
+    // Number of distinct positions recorded
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn record(&mut self, key: u64, results: &[f64]) {
+        let entry = self.entries.entry(key).or_default();
+        entry.visit_count += 1;
+        entry.total_values.resize(results.len(), 0.);
+        entry.total_values.iter_mut().zip(results).for_each(|(total, result)| *total += result);
+    }
+}
+
+pub(crate) fn mean_value(total_values: &[f64], player: usize, visit_count: i32) -> Option<f64> {
+    if visit_count > 0 { Some(total_values[player] / (visit_count as f64)) } else { None }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult<Action> {
+    // None if the root was terminal
+    pub action: Option<Action>,
+    // Visit count of the chosen move, and its mean value for the player to move at the root
+    pub visits: i32,
+    pub value: f64,
+    pub nodes_expanded: usize,
+    pub elapsed: Duration,
+}
+
+// How much of the tree survived advancing past a move
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AdvanceStats {
+    // Visits of the subtree kept as the new root, and of everything else under the old root
+    pub reused_visits: i32,
+    pub discarded_visits: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChildStat<Action> {
+    pub action: Action,
+    pub visit_count: i32,
+    // Mean value of the move for the player to move at the root, 0 if unvisited
+    pub mean_value: f64,
+    pub prior: f64,
+}
+
+// Score of a child from the perspective of its parent's player to move, given the child's stats and prior. `value` is
+// the child's mean in [-1, 1] for that player (see Node::value_for_parent()), and is mapped to [0, 1] here.
+pub(crate) fn selection_score(config: &SearchConfig, node_visit: i32, visit_count: i32, value: Option<f64>, prior: f64) -> f64 {
+    let value_score = (value.unwrap_or(config.first_play_urgency) + 1.) / 2.;
+
+    let score = match config.selection_policy {
+        SelectionPolicy::Ucb1 { .. } if visit_count == 0 => f64::INFINITY,
+        SelectionPolicy::Ucb1 { c } => value_score + c * ((node_visit.max(1) as f64).ln() / visit_count as f64).sqrt(),
+        SelectionPolicy::Puct => {
+            let pb_c = config.pb_c_init + ((node_visit as f64 + config.pb_c_base + 1.) / config.pb_c_base).ln();
+            let policy_score = (node_visit as f64).sqrt() * pb_c * prior / (visit_count as f64 + 1.);
+            value_score + policy_score
+        },
+    };
+    score + progressive_bias(config, visit_count, prior)
+}
+
+// The SearchConfig::progressive_bias term of a child's selection score, which shrinks as its visits grow
+fn progressive_bias(config: &SearchConfig, visit_count: i32, prior: f64) -> f64 {
+    if config.progressive_bias == 0. {
+        return 0.;  // Keeps a NaN prior from turning the score NaN when the bias is off
+    }
+    config.progressive_bias * prior / (visit_count as f64 + 1.)
+}
+
+// 0 if `player` has already won, or 1 if it's their move and they can win with it
+fn immediate_win_distance<State: GameState>(state: &State, player: i32) -> Option<usize> {
+    if state.is_terminal() {
+        return if state.status() == GameStatus::Win(player) { Some(0) } else { None };
+    }
+    let wins = state.get_player_turn() == player && state.get_legal_actions().into_iter()
+        .any(|action| state.get_next_state(action).status() == GameStatus::Win(player));
+    if wins { Some(1) } else { None }
+}
+
+// Orders scores with NaN below everything else, so a child scored NaN by a buggy policy or value is never preferred
+// to one with a real score, rather than panicking mid-search
+pub(crate) fn compare_scores(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (false, false) => a.partial_cmp(&b).unwrap(),
+        (nan_a, nan_b) => nan_b.cmp(&nan_a),
+    }
+}
+
+// A move that wins on the spot, or failing that the most likely move under the prior if every move loses on the spot
+// or lets the opponent win with their reply, since no amount of searching will change the outcome
+pub(crate) fn decided_action<State: GameState>(state: &State) -> Option<State::Action> {
+    let player = state.get_player_turn();
+    let actions = state.get_legal_actions();
+    let next_states: Vec<State> = actions.iter().map(|&action| state.get_next_state(action)).collect();
+    if let Some(i) = next_states.iter().position(|next_state| next_state.status() == GameStatus::Win(player)) {
+        return Some(actions[i]);
+    }
+
+    if actions.is_empty() || !next_states.iter().all(|next_state| loses_at_once(next_state, player)) {
+        return None;
+    }
+    let priors = state.get_policy(&actions);
+    (0..actions.len()).max_by(|&a, &b| compare_scores(priors[a], priors[b])).map(|i| actions[i])
+}
+
+// Rejects the options that only Node follows, for the other trees: max_nodes and expand_one change the shape of the
+// tree as it grows, which the arena and SharedNode don't do. `tree` names the one asking, for the error.
+pub(crate) fn check_supported(config: &SearchConfig, tree: &str) -> Result<(), ConfigError> {
+    let unsupported = [("max_nodes", config.max_nodes.is_some()), ("expand_one", config.expand_one)];
+    match unsupported.into_iter().find(|&(_, set)| set) {
+        Some((option, _)) => Err(ConfigError(format!("{} doesn't support {}", tree, option))),
+        None => Ok(()),
+    }
+}
+
+// The move to play without searching, if the config allows for one: a decided_action() with quick_win, or an
+// endgame_action() within endgame_exact_depth
+pub(crate) fn shortcut_action<State: GameState>(state: &State, config: &SearchConfig) -> Option<State::Action> {
+    let decided = if config.quick_win { decided_action(state) } else { None };
+    decided.or_else(|| config.endgame_exact_depth.and_then(|max_depth| endgame_action(state, max_depth)))
+}
+
+// The move to play after searching `state`, given the stats of its children in the tree: a random legal move at
+// the blunder rate, otherwise the most visited or best valued child at temperature 0, or one sampled by visits.
+// Shared by every tree, so they all honour the same settings.
+pub(crate) fn final_action<State: GameState, R: Rng>(state: &State, children: &[ChildStat<State::Action>], config: &SearchConfig, rng: &mut R) -> Option<State::Action> {
+    // The rng is only drawn from when blundering is on, so other searches stay reproducible as they were
+    if config.blunder_rate > 0. && rng.gen_bool(config.blunder_rate.min(1.)) {
+        if let Some(&action) = state.get_legal_actions().choose(rng) {
+            return Some(action);
+        }
+    }
+    let in_opening = state.move_count().is_some_and(|moves| moves < config.opening_moves);
+    let temperature = if in_opening { 1. } else { config.temperature };
+    let most_visited = || children.iter().max_by_key(|child| child.visit_count).map(|child| child.action);
+    if temperature < 1e-3 {
+        return match config.final_selection {
+            FinalSelection::MaxVisits => most_visited(),
+            FinalSelection::MaxValue { min_visits } => best_valued_action(children, min_visits).or_else(most_visited),
+        };
+    }
+
+    // Sample proportionally to visit_count^(1/temperature), scaled by the max count to avoid overflow
+    let max_visits = children.iter().fold(0., |a: f64, child| a.max(child.visit_count as f64));
+    if max_visits == 0. {
+        return most_visited();
+    }
+    let weights: Vec<f64> = children.iter().map(|child| (child.visit_count as f64 / max_visits).powf(1. / temperature)).collect();
+    let dist = WeightedIndex::new(&weights).unwrap();
+    Some(children[dist.sample(rng)].action)
+}
+
+// The child with the highest mean value for the player to move, among those visited at least `min_visits` times
+fn best_valued_action<Action: Copy>(children: &[ChildStat<Action>], min_visits: i32) -> Option<Action> {
+    children.iter()
+        .filter(|child| child.visit_count >= min_visits.max(1))
+        .max_by(|a, b| compare_scores(a.mean_value, b.mean_value))
+        .map(|child| child.action)
+}
+
+// Positions endgame_action() gives up after, so a depth set too high for the position costs a bounded amount of time
+const ENDGAME_NODE_LIMIT: usize = 1_000_000;
+
+// The best move for the player to move by exact minimax with alpha-beta pruning, or None if a line it needs runs
+// past `max_depth` moves, it would take more than ENDGAME_NODE_LIMIT positions, or the game isn't a two-player game
+// without chance nodes. Deep positions give up at the end of the first line, so trying costs little. Results are
+// get_result(), with wins worth a little more the sooner they come and losses the later, so a won game is won
+// quickly and a lost one drawn out. Ties go to the first move.
+pub fn endgame_action<State: GameState>(state: &State, max_depth: usize) -> Option<State::Action> {
+    if state.is_terminal() || state.num_players() != 2 || state.is_chance_node() || max_depth == 0 {
+        return None;
+    }
+    let maximizing = state.get_player_turn() == 1;
+    let (mut alpha, mut beta) = (f64::NEG_INFINITY, f64::INFINITY);
+    let mut nodes = 0;
+    let mut best: Option<(State::Action, f64)> = None;
+    for action in state.get_legal_actions() {
+        let value = exact_value(&state.get_next_state(action), max_depth - 1, 1, alpha, beta, &mut nodes)?;
+        if best.is_none_or(|(_, best_value)| if maximizing { value > best_value } else { value < best_value }) {
+            best = Some((action, value));
+        }
+        if maximizing { alpha = alpha.max(value) } else { beta = beta.min(value) }
+    }
+    best.map(|(action, _)| action)
+}
+
+// Minimax value of `state` in get_result()'s perspective, `ply` moves below the root
+fn exact_value<State: GameState>(state: &State, depth_left: usize, ply: usize, mut alpha: f64, mut beta: f64, nodes: &mut usize) -> Option<f64> {
+    *nodes += 1;
+    if state.is_terminal() {
+        return Some(state.get_result() / (1. + 1e-3 * ply as f64));
+    }
+    let actions = state.get_legal_actions();
+    if depth_left == 0 || *nodes > ENDGAME_NODE_LIMIT || state.is_chance_node() || actions.is_empty() {
+        return None;
+    }
+    // The player to move can be the same as before, in games with extra turns
+    let maximizing = state.get_player_turn() == 1;
+    let mut best = if maximizing { f64::NEG_INFINITY } else { f64::INFINITY };
+    for action in actions {
+        let value = exact_value(&state.get_next_state(action), depth_left - 1, ply + 1, alpha, beta, nodes)?;
+        if maximizing {
+            best = best.max(value);
+            alpha = alpha.max(value);
+        } else {
+            best = best.min(value);
+            beta = beta.min(value);
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+    Some(best)
+}
+
+// Whether `player`, having just moved into `next_state`, has lost or lets the opponent win with their reply
+fn loses_at_once<State: GameState>(next_state: &State, player: i32) -> bool {
+    match next_state.status() {
+        GameStatus::Win(winner) => winner != player,
+        GameStatus::Draw => false,
+        GameStatus::InProgress => {
+            let opponent = next_state.get_player_turn();
+            next_state.get_legal_actions().into_iter()
+                .any(|reply| next_state.get_next_state(reply).status() == GameStatus::Win(opponent))
+        },
+    }
+}
+
+// Drops the moves that lose at once from a node's (action, next state) pairs, unless that would drop them all
+pub(crate) fn prune_losing_moves<State: GameState>(player: i32, children: &mut Vec<(State::Action, State)>) {
+    let losing: Vec<bool> = children.iter().map(|(_, next_state)| loses_at_once(next_state, player)).collect();
+    if losing.iter().all(|&loses| loses) {
+        return;
+    }
+    let mut losing = losing.into_iter();
+    children.retain(|_| !losing.next().unwrap());
+}
+
+// The move replay() couldn't apply, either because it isn't legal or because the game had already ended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IllegalMove<Action> {
+    // Position of the move in the list passed to replay()
+    pub index: usize,
+    pub action: Action,
+}
+
+impl<Action: Debug> fmt::Display for IllegalMove<Action> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} ({:?}) is not legal", self.index + 1, self.action)
+    }
+}
+
+impl<Action: Debug> std::error::Error for IllegalMove<Action> {}
+
+// Plays `actions` from `initial_state`, returning every state along the way starting with the initial one
+pub fn replay<State: GameState + Clone>(initial_state: State, actions: &[State::Action]) -> Result<Vec<State>, IllegalMove<State::Action>> {
+    let mut states = vec![initial_state];
+    for (index, &action) in actions.iter().enumerate() {
+        let state = states.last().unwrap();
+        if state.is_terminal() || !state.get_legal_actions().contains(&action) {
+            return Err(IllegalMove { index, action });
+        }
+        let next_state = state.get_next_state(action);
+        states.push(next_state);
+    }
+    Ok(states)
+}
+
+// Generator for searches that aren't given one. Without the thread_rng feature there's no entropy source to seed it
+// from, so it falls back to a fixed seed; pass your own generator to best_action_with_rng() in that case.
+#[cfg(feature = "thread_rng")]
+pub(crate) fn default_rng() -> ThreadRng {
+    rand::thread_rng()
+}
+
+#[cfg(not(feature = "thread_rng"))]
+pub(crate) fn default_rng() -> StdRng {
+    StdRng::seed_from_u64(0)
+}
+
+// Per-player values of a leaf, averaged over config.rollouts_per_leaf playouts
+pub(crate) fn rollout<State: GameState + Clone, R: Rng>(state: &State, config: &SearchConfig, rng: &mut R) -> Vec<f64> {
+    let n_rollouts = config.rollouts_per_leaf.max(1);
+    let mut totals = playout(state, config, rng);
+    for _ in 1..n_rollouts {
+        for (total, result) in totals.iter_mut().zip(playout(state, config, rng)) {
+            *total += result;
+        }
+    }
+    let mut results: Vec<f64> = totals.iter().map(|total| total / n_rollouts as f64).collect();
+    apply_komi(config, &mut results);
+    results
+}
+
+fn apply_komi(config: &SearchConfig, results: &mut [f64]) {
+    if let [first, second] = results {
+        *first -= config.komi;
+        *second += config.komi;
+    }
+}
+
+// Picks one of a chance node's outcomes by probability, or uniformly if the probabilities are unusable
+pub(crate) fn sample_index<R: Rng>(weights: &[f64], rng: &mut R) -> usize {
+    match WeightedIndex::new(weights) {
+        Ok(dist) => dist.sample(rng),
+        Err(_) => rng.gen_range(0..weights.len()),
+    }
+}
+
+fn playout<State: GameState + Clone, R: Rng>(state: &State, config: &SearchConfig, rng: &mut R) -> Vec<f64> {
+    let mut current_state = state.clone();
+    let mut depth = 0;
+    while !current_state.is_terminal() {
+        if config.max_rollout_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return current_state.evaluate_results();
+        }
+        if depth >= config.max_rollout_moves {
+            return vec![0.; current_state.num_players()];
+        }
+        if let Some(cutoff) = config.rollout_confidence_cutoff {
+            let results = current_state.evaluate_results();
+            if results.iter().any(|result| result.abs() >= cutoff) {
+                return results;
+            }
+        }
+
+        if current_state.is_chance_node() {
+            let mut outcomes = current_state.get_chance_outcomes();
+            if outcomes.is_empty() {
+                break;
+            }
+            let weights: Vec<f64> = outcomes.iter().map(|(_, probability)| *probability).collect();
+            current_state = outcomes.swap_remove(sample_index(&weights, rng)).0;
+            depth += 1;
+            continue;
+        }
+
+        let actions = current_state.get_legal_actions();
+        if actions.is_empty() {
+            break;  // A non-terminal state with no moves is scored as it stands
+        }
+        // Weights don't need to be normalized
+        let weights = current_state.get_rollout_policy(&actions);
+        match config.rollout_strategy.choose(&weights, rng) {
+            Some(i) => current_state = current_state.get_next_state(actions[i]),
+            None => return current_state.evaluate_results(),
+        }
+        depth += 1;
+    }
+    current_state.get_results()
 }
 
 pub struct Node<State: GameState> {
@@ -22,29 +788,104 @@ pub struct Node<State: GameState> {
     parent: Option<Weak<RefCell<Self>>>,
     parent_action: Option<State::Action>,
     children: Vec<Rc<RefCell<Self>>>,
-    results: HashMap<i32, i32>,
+    total_values: Vec<f64>,
     visit_count: i32,
-    action_probs: HashMap<State::Action, f64>,
+    virtual_loss: i32,
+    action_probs: Option<HashMap<State::Action, f64>>,
+    chance_weights: Vec<f64>,  // Probabilities of the children of a chance node, which have no parent_action
+    subtree_size: usize,  // Nodes in the tree below and including this one
+    pending_actions: Vec<State::Action>,  // Moves still to be added as children with expand_one, highest prior last
+}
+
+// The derived drop would recurse once per tree level, which can overflow the stack on very deep trees. Instead,
+// children only referenced from here are unlinked into a worklist and dropped one at a time.
+impl<State: GameState> Drop for Node<State> {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(child) = stack.pop() {
+            if let Ok(child) = Rc::try_unwrap(child) {
+                stack.append(&mut child.into_inner().children);
+            }
+        }
+    }
 }
 
 impl<State: GameState + Clone> Node<State> {
     pub fn new(state: State, parent: Option<Weak<RefCell<Self>>>, parent_action: Option<State::Action>) -> Rc<RefCell<Self>> {
-        let actions = state.get_legal_actions();
-        let weights = state.get_policy(&actions);
-        let action_probs = actions.clone().into_iter().zip(weights.into_iter()).collect::<HashMap<_, _>>();
         Rc::new(RefCell::new(Node {
+            total_values: vec![0.; state.num_players()],
             state,
             parent,
             parent_action,
             children: Vec::new(),
-            results: HashMap::new(),
             visit_count: 0,
-            action_probs,
+            virtual_loss: 0,
+            action_probs: None,
+            chance_weights: Vec::new(),
+            subtree_size: 1,
+            pending_actions: Vec::new(),
         }))
     }
 
-    fn q(&self) -> f64 {
-        if self.visit_count > 0 { (*self.results.get(&1).unwrap_or(&0) as f64 - *self.results.get(&-1).unwrap_or(&0) as f64) / (self.visit_count as f64) } else { -1. }
+    // Priors are only computed once a node is expanded, since most leaves never are
+    fn action_probs(&mut self) -> &mut HashMap<State::Action, f64> {
+        self.action_probs.get_or_insert_with(|| {
+            let actions = self.state.get_legal_actions();
+            let weights = self.state.get_policy(&actions);
+            actions.into_iter().zip(weights).collect()
+        })
+    }
+
+    // Mean value in [-1, 1] for the player to move at this node, or -1 if it hasn't been visited. In a two-player
+    // game this is the negation of value_for_parent().
+    pub fn value(&self) -> f64 {
+        mean_value(&self.total_values, self.state.get_player_index(), self.visit_count).unwrap_or(-1.)
+    }
+
+    pub fn visits(&self) -> i32 {
+        self.visit_count
+    }
+
+    // Sum of the backpropagated values for each player, indexed like get_player_index()
+    pub fn total_values(&self) -> &[f64] {
+        &self.total_values
+    }
+
+    // Mean value in [-1, 1] for the player who moved into this node, which is what its parent compares it to its
+    // siblings by. None if it hasn't been visited or has no parent. selection_score() maps it into [0, 1].
+    pub fn value_for_parent(&self) -> Option<f64> {
+        self.parent_stats(None).1
+    }
+
+    // stats() from the perspective of the player to move at the parent, or an unvisited node's if it's the root
+    fn parent_stats(&self, table: Option<&TranspositionTable>) -> (i32, Option<f64>) {
+        match self.parent.as_ref().and_then(|parent| parent.upgrade()) {
+            Some(parent) => self.stats(table, parent.borrow().state.get_player_index()),
+            None => (self.visit_count, None),
+        }
+    }
+
+    // Visit count and mean value for the given player, taken from the transposition table when one is in use
+    fn stats(&self, table: Option<&TranspositionTable>, player: usize) -> (i32, Option<f64>) {
+        let (visit_count, value_sum) = match table.and_then(|table| table.entries.get(&self.state.canonical_key())) {
+            Some(entry) => (entry.visit_count, entry.total_values[player]),
+            None => (self.visit_count, self.total_values[player]),
+        };
+
+        // Pending virtual losses count as extra visits lost by whoever is selecting this node
+        let visit_count = visit_count + self.virtual_loss;
+        let value = if visit_count > 0 { Some((value_sum - self.virtual_loss as f64) / visit_count as f64) } else { None };
+        (visit_count, value)
+    }
+
+    // Temporarily penalize this node so concurrent selections are steered elsewhere until the matching revert
+    pub fn apply_virtual_loss(&mut self) {
+        self.virtual_loss += 1;
+    }
+
+    pub fn revert_virtual_loss(&mut self) {
+        assert!(self.virtual_loss > 0, "Attempted to revert a virtual loss that was never applied.");
+        self.virtual_loss -= 1;
     }
 
     fn is_terminal(&self) -> bool {
@@ -53,90 +894,184 @@ impl<State: GameState + Clone> Node<State> {
 
     // Step 1: Select and expand
 
-    fn select_node(node: &Rc<RefCell<Self>>) -> Rc<RefCell<Self>> {
+    // Returns the leaf to roll out from, and whether a node was expanded on the way to it
+    fn select_node<R: Rng>(node: &Rc<RefCell<Self>>, config: &SearchConfig, table: Option<&TranspositionTable>, rng: &mut R) -> (Rc<RefCell<Self>>, bool) {
         let mut current_node = Rc::clone(node);
 
         while !current_node.borrow().is_terminal() {
             if current_node.borrow().children.is_empty() {
-                Node::expand(&current_node);
-                return current_node.borrow().best_child();
+                Node::expand(&current_node, config);
+                if current_node.borrow().children.is_empty() {
+                    return (current_node, true);  // No legal moves, so treat it as a terminal leaf
+                }
+                let child = current_node.borrow().next_child(config, table, rng);
+                return (child, true);
+            } else if !current_node.borrow().pending_actions.is_empty() {
+                return (Node::expand_next(&current_node), true);
             } else {
-                let next_node = current_node.borrow().best_child();
+                let next_node = current_node.borrow().next_child(config, table, rng);
                 current_node = next_node;
             }
         }
 
-        current_node
+        (current_node, false)
     }
 
-    fn best_child(&self) -> Rc<RefCell<Self>> {
-        self.children.iter().max_by(|a, b| {
-            let a_score = a.borrow().uct_score(self.visit_count);
-            let b_score = b.borrow().uct_score(self.visit_count);
-            a_score.partial_cmp(&b_score).expect("Unable to compare scores, NaN or infinity encountered.")
-        }).map(|node| Rc::clone(node)).expect("Unable to find best child node")
+    // The child to descend into: sampled by probability at a chance node, otherwise the best by selection score
+    fn next_child<R: Rng>(&self, config: &SearchConfig, table: Option<&TranspositionTable>, rng: &mut R) -> Rc<RefCell<Self>> {
+        if self.chance_weights.is_empty() {
+            self.best_child(config, table)
+        } else {
+            Rc::clone(&self.children[sample_index(&self.chance_weights, rng)])
+        }
     }
 
-    fn uct_score(&self, node_visit: i32) -> f64 {
+    fn best_child(&self, config: &SearchConfig, table: Option<&TranspositionTable>) -> Rc<RefCell<Self>> {
+        let player = self.state.get_player_index();
+        let (node_visit, _) = self.stats(table, player);
+        let prior = |child: &Self| self.action_probs.as_ref().and_then(|probs| probs.get(&child.parent_action?).copied()).unwrap_or(0.);
+        // Ties go to the higher prior, then to the child expanded first. max_by() keeps the last of equal
+        // elements, so the children are walked in reverse.
+        self.children.iter().rev().max_by(|a, b| {
+            let (a, b) = (a.borrow(), b.borrow());
+            let a_score = a.uct_score(node_visit, config, table);
+            let b_score = b.uct_score(node_visit, config, table);
+            compare_scores(a_score, b_score).then_with(|| compare_scores(prior(&a), prior(&b)))
+        }).map(Rc::clone).expect("Unable to find best child node")
+    }
+
+    // Score of this child from the perspective of the parent's player to move
+    fn uct_score(&self, node_visit: i32, config: &SearchConfig, table: Option<&TranspositionTable>) -> f64 {
+        let (visit_count, value) = self.parent_stats(table);
         let action_prob = self.parent.as_ref().and_then(|parent_weak| parent_weak.upgrade()).map_or(0.0, |parent| {
-            parent.borrow().action_probs.get(&self.parent_action.unwrap()).copied().unwrap_or(0.0)
+            parent.borrow().action_probs.as_ref().and_then(|probs| probs.get(&self.parent_action.unwrap()).copied()).unwrap_or(0.0)
         });
-
-        let pb_c_init = 1.25;
-        let pb_c_base = 19652.;
-        let pb_c = pb_c_init + ((node_visit as f64 + pb_c_base + 1.) / pb_c_base).ln();
-        let policy_score = (node_visit as f64).sqrt() * pb_c * action_prob / (self.visit_count as f64 + 1.);
-        let value_score = (-self.q() + 1.) / 2.;
-        value_score + policy_score
+        selection_score(config, node_visit, visit_count, value, action_prob)
     }
 
-    fn expand(node: &Rc<RefCell<Self>>) {
+    fn expand(node: &Rc<RefCell<Self>>, config: &SearchConfig) {
         let mut parent = node.borrow_mut();
         assert!(!parent.is_terminal(), "Attempted to expand a terminal node.");
         assert!(parent.children.is_empty(), "Attempted to re-expand a node.");
 
         let state = parent.state.clone();
-        for &action in state.get_legal_actions().iter() {
-            if let Some(&prob) = parent.action_probs.get(&action) {
-                if prob > 1e-6 {
-                    let child_state = state.get_next_state(action);
-                    let child_node = Node::new(child_state, Some(Rc::downgrade(node)), Some(action));
-                    parent.children.push(child_node);
-                }
+        if state.is_chance_node() {
+            for (child_state, probability) in state.get_chance_outcomes() {
+                parent.children.push(Node::new(child_state, Some(Rc::downgrade(node)), None));
+                parent.chance_weights.push(probability);
+            }
+        } else {
+            let actions = state.get_legal_actions();
+            // Compute the priors from the same actions, unless they're already there (e.g. with noise added at the root)
+            if parent.action_probs.is_none() {
+                let weights = state.get_policy(&actions);
+                parent.action_probs = Some(actions.iter().copied().zip(weights).collect());
+            }
+            // Moves with a NaN prior are kept, for best_child() to rank last, so a buggy policy can't leave a node
+            // with no moves at all
+            let mut children: Vec<(State::Action, State)> = actions.iter()
+                .filter(|action| parent.action_probs().get(action).is_some_and(|&prob| prob > 1e-6 || prob.is_nan()))
+                .map(|&action| (action, state.get_next_state(action)))
+                .collect();
+            if config.prune_losing_moves {
+                prune_losing_moves(state.get_player_turn(), &mut children);
+            }
+            if config.expand_one {
+                // Only the highest prior move becomes a child for now, the first of equals as with best_child()
+                let priors = parent.action_probs();
+                children.sort_by(|(a, _), (b, _)| compare_scores(priors[b], priors[a]));
+                parent.pending_actions = children.drain(1.min(children.len())..).rev().map(|(action, _)| action).collect();
+            }
+            for (action, child_state) in children {
+                let child_node = Node::new(child_state, Some(Rc::downgrade(node)), Some(action));
+                parent.children.push(child_node);
             }
         }
+
+        let added = parent.children.len() as isize;
+        drop(parent);
+        Node::resize_subtrees(node, added);
     }
 
-    // Step 2: Rollout to the end of the game
+    // Adds the next of a partly expanded node's children, for expand_one, and returns it
+    fn expand_next(node: &Rc<RefCell<Self>>) -> Rc<RefCell<Self>> {
+        let child = {
+            let mut parent = node.borrow_mut();
+            let action = parent.pending_actions.pop().expect("No children left to expand");
+            let child = Node::new(parent.state.get_next_state(action), Some(Rc::downgrade(node)), Some(action));
+            parent.children.push(Rc::clone(&child));
+            child
+        };
+        Node::resize_subtrees(node, 1);
+        child
+    }
 
-    fn rollout(&self) -> i32 {
-        let mut current_state = self.state.clone();
-        while !current_state.is_terminal() {
-            let actions = current_state.get_legal_actions();
-            let weights = current_state.get_policy(&actions);
-            let weight_sum: f64 = weights.iter().sum();
-            assert!((weight_sum - 1.0).abs() < 1e-6, "Policy weights do not sum to 1: {:?}", weights);
+    // Adds `delta` to the subtree sizes of a node and everything above it
+    fn resize_subtrees(node: &Rc<RefCell<Self>>, delta: isize) {
+        let mut current_node_option = Some(Rc::clone(node));
+        while let Some(current_node_rc) = current_node_option {
+            let mut current_node = current_node_rc.borrow_mut();
+            current_node.subtree_size = current_node.subtree_size.saturating_add_signed(delta);
+            current_node_option = current_node.parent.as_ref().and_then(Weak::upgrade);
+        }
+    }
 
-            let dist = WeightedIndex::new(&weights).unwrap();
-            let action = actions[dist.sample(&mut rand::thread_rng())];
-            current_state = current_state.get_next_state(action);
+    // Keeps the tree within config.max_nodes by dropping the children of the least-visited nodes below the root.
+    // Deeper nodes go first among equals, so a subtree is always cut before anything above it.
+    fn enforce_node_budget(root: &Rc<RefCell<Self>>, config: &SearchConfig) {
+        let Some(max_nodes) = config.max_nodes else { return };
+        if root.borrow().subtree_size <= max_nodes {
+            return;
+        }
+
+        let mut internal_nodes = Vec::new();
+        let mut stack: Vec<(Rc<RefCell<Self>>, usize)> = root.borrow().children.iter().map(|child| (Rc::clone(child), 1)).collect();
+        while let Some((node, depth)) = stack.pop() {
+            if !node.borrow().children.is_empty() {
+                stack.extend(node.borrow().children.iter().map(|child| (Rc::clone(child), depth + 1)));
+                internal_nodes.push((node, depth));
+            }
+        }
+        internal_nodes.sort_by_key(|(node, depth)| (node.borrow().visit_count, std::cmp::Reverse(*depth)));
+
+        let target = max_nodes - max_nodes / 4;
+        for (node, _) in internal_nodes {
+            if root.borrow().subtree_size <= target {
+                break;
+            }
+            let removed = {
+                let mut node = node.borrow_mut();
+                node.children.clear();
+                node.chance_weights.clear();
+                node.pending_actions.clear();
+                std::mem::replace(&mut node.subtree_size, 1) - 1
+            };
+            if let Some(parent) = node.borrow().parent.as_ref().and_then(Weak::upgrade) {
+                Node::resize_subtrees(&parent, -(removed as isize));
+            }
         }
-        let result = current_state.get_result();
-        if self.state.get_player_turn() == current_state.get_player_turn() { result } else { -result }
+    }
+
+    // Step 2: Rollout to the end of the game
+
+    fn rollout<R: Rng>(&self, config: &SearchConfig, rng: &mut R) -> Vec<f64> {
+        rollout(&self.state, config, rng)
     }
 
     // Step 3: Backpropagate the results
 
-    fn backpropagate(node: &Rc<RefCell<Self>>, result: i32) {
+    fn backpropagate(node: &Rc<RefCell<Self>>, results: &[f64], mut table: Option<&mut TranspositionTable>) {
         let mut current_node_option = Some(Rc::clone(node));
-        let mut current_result = result;
 
+        // Every node accumulates the values for all players, so each one can be read from the perspective of whoever moves there.
         while let Some(current_node_rc) = current_node_option {
             let mut current_node = current_node_rc.borrow_mut();
             current_node.visit_count += 1;
-            *current_node.results.entry(current_result).or_insert(0) += 1;
+            current_node.total_values.iter_mut().zip(results).for_each(|(total, result)| *total += result);
+            if let Some(table) = table.as_deref_mut() {
+                table.record(current_node.state.canonical_key(), results);
+            }
 
-            current_result *= -1;  // Flip the result for the next level up, since it's from the opponent's perspective.
             current_node_option = match current_node.parent {
                 Some(ref parent_weak) => parent_weak.upgrade(),
                 None => None,
@@ -146,15 +1081,588 @@ impl<State: GameState + Clone> Node<State> {
 
     // Tree search
 
-    pub fn best_action(root: &Rc<RefCell<Self>>, n_simulations: i32) -> State::Action {
-        for _ in 0..n_simulations {
-            let leaf_node = Node::select_node(root);
-            let result = leaf_node.borrow().rollout();
-            Node::backpropagate(&leaf_node, result);
+    pub fn best_action(root: &Rc<RefCell<Self>>, n_simulations: i32) -> Option<State::Action> {
+        let config = SearchConfig { n_simulations, ..SearchConfig::default() };
+        Node::best_action_with_config(root, &config)
+    }
+
+    pub fn best_action_with_config(root: &Rc<RefCell<Self>>, config: &SearchConfig) -> Option<State::Action> {
+        Node::search(root, config).action
+    }
+
+    // Like best_action_with_config(), but also reports how the search went
+    pub fn search(root: &Rc<RefCell<Self>>, config: &SearchConfig) -> SearchResult<State::Action> {
+        let start = Instant::now();
+        let mut nodes_expanded = 0;
+        let action = Node::run_search(root, config, &mut default_rng(), &mut nodes_expanded);
+        let stat = action.and_then(|action| Node::child_stats(root).into_iter().find(|stat| stat.action == action));
+        SearchResult {
+            action,
+            visits: stat.as_ref().map_or(0, |stat| stat.visit_count),
+            value: stat.as_ref().map_or(0., |stat| stat.mean_value),
+            nodes_expanded,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    pub fn best_action_seeded(root: &Rc<RefCell<Self>>, n_simulations: i32, seed: u64) -> Option<State::Action> {
+        let config = SearchConfig { n_simulations, ..SearchConfig::default() };
+        Node::best_action_with_rng(root, &config, &mut StdRng::seed_from_u64(seed))
+    }
+
+    // Draws all randomness from `rng`, so a seeded generator makes whole games reproducible
+    pub fn best_action_with_rng<R: Rng>(root: &Rc<RefCell<Self>>, config: &SearchConfig, rng: &mut R) -> Option<State::Action> {
+        let mut nodes_expanded = 0;
+        Node::run_search(root, config, rng, &mut nodes_expanded)
+    }
+
+    fn run_search<R: Rng>(root: &Rc<RefCell<Self>>, config: &SearchConfig, rng: &mut R, nodes_expanded: &mut usize) -> Option<State::Action> {
+        if root.borrow().is_terminal() {
+            return None;
+        }
+        if let Some(action) = shortcut_action(&root.borrow().state, config) {
+            return Some(action);
+        }
+        for _ in 0..config.n_simulations {
+            if Node::simulate(root, config, None, rng) {
+                *nodes_expanded += 1;
+            }
+        }
+        Node::select_final_action(root, config, rng)
+    }
+
+    pub fn best_action_temperature(root: &Rc<RefCell<Self>>, n_simulations: i32, temperature: f64) -> Option<State::Action> {
+        let config = SearchConfig { n_simulations, temperature, ..SearchConfig::default() };
+        Node::best_action_with_config(root, &config)
+    }
+
+    pub fn best_action_with_depth(root: &Rc<RefCell<Self>>, n_simulations: i32, max_depth: usize) -> Option<State::Action> {
+        let config = SearchConfig { n_simulations, max_rollout_depth: Some(max_depth), ..SearchConfig::default() };
+        Node::best_action_with_config(root, &config)
+    }
+
+    pub fn best_action_with_progress(root: &Rc<RefCell<Self>>, n_simulations: i32, mut callback: impl FnMut(usize, usize)) -> Option<State::Action> {
+        if root.borrow().is_terminal() {
+            return None;
+        }
+        let config = SearchConfig { n_simulations, ..SearchConfig::default() };
+        let total = n_simulations.max(0) as usize;
+        let interval = (total / 100).max(1);
+        let mut rng = default_rng();
+        for completed in 1..=total {
+            Node::simulate(root, &config, None, &mut rng);
+            if completed % interval == 0 || completed == total {
+                callback(completed, total);
+            }
+        }
+        Node::select_final_action(root, &config, &mut rng)
+    }
+
+    // Anytime search: grows the tree to schedule[0] simulations and reports the best action so far, then keeps
+    // growing the same tree to schedule[1], and so on. The callback gets the number of simulations run and the
+    // action, and returns whether to carry on. Returns the last action reported.
+    pub fn best_action_iterative(root: &Rc<RefCell<Self>>, schedule: &[i32], callback: impl FnMut(i32, State::Action) -> bool) -> Option<State::Action> {
+        Node::best_action_iterative_with_rng(root, &SearchConfig::default(), schedule, &mut default_rng(), callback)
+    }
+
+    // Like best_action_iterative(), but with the given config (apart from n_simulations, which the schedule
+    // replaces) and randomness. The last tier then picks the same action as a single search of that size, unless
+    // the final move is sampled.
+    pub fn best_action_iterative_with_rng<R: Rng>(root: &Rc<RefCell<Self>>, config: &SearchConfig, schedule: &[i32], rng: &mut R, mut callback: impl FnMut(i32, State::Action) -> bool) -> Option<State::Action> {
+        if root.borrow().is_terminal() {
+            return None;
+        }
+        if let Some(action) = shortcut_action(&root.borrow().state, config) {
+            callback(0, action);
+            return Some(action);
+        }
+        let mut completed = 0;
+        let mut best_action = None;
+        for &tier in schedule {
+            while completed < tier {
+                Node::simulate(root, config, None, rng);
+                completed += 1;
+            }
+            best_action = Node::select_final_action(root, config, rng);
+            match best_action {
+                Some(action) if callback(completed, action) => {},
+                _ => break,
+            }
+        }
+        best_action
+    }
+
+    pub fn best_action_with_table(root: &Rc<RefCell<Self>>, config: &SearchConfig, table: &mut TranspositionTable) -> Option<State::Action> {
+        if root.borrow().is_terminal() {
+            return None;
+        }
+        if let Some(action) = shortcut_action(&root.borrow().state, config) {
+            return Some(action);
+        }
+        let mut rng = default_rng();
+        for _ in 0..config.n_simulations {
+            Node::simulate(root, config, Some(table), &mut rng);
+        }
+        Node::select_final_action(root, config, &mut rng)
+    }
+
+    // Searches with `provider` evaluating each new leaf, so there are no rollouts and get_policy() isn't used.
+    // Terminal leaves are still scored with get_results().
+    pub fn best_action_with_provider(root: &Rc<RefCell<Self>>, config: &SearchConfig, provider: &dyn ValuePolicyProvider<State>) -> Option<State::Action> {
+        if root.borrow().is_terminal() {
+            return None;
+        }
+        if let Some(action) = shortcut_action(&root.borrow().state, config) {
+            return Some(action);
+        }
+        let mut rng = default_rng();
+        for _ in 0..config.n_simulations {
+            Node::simulate_with_provider(root, config, provider, &mut rng);
+        }
+        Node::select_final_action(root, config, &mut rng)
+    }
+
+    pub fn best_action_timed(root: &Rc<RefCell<Self>>, budget: Duration) -> Option<State::Action> {
+        if root.borrow().is_terminal() {
+            return None;
+        }
+        let config = SearchConfig::default();
+        let mut rng = default_rng();
+        let deadline = Instant::now() + budget;
+        while Instant::now() < deadline {
+            Node::simulate(root, &config, None, &mut rng);
+        }
+        Node::select_final_action(root, &config, &mut rng)
+    }
+
+    // Runs simulations without choosing a move, growing the tree ahead of a later search from the same root
+    pub fn ponder(root: &Rc<RefCell<Self>>, config: &SearchConfig) {
+        if root.borrow().is_terminal() {
+            return;
+        }
+        let mut rng = default_rng();
+        for _ in 0..config.n_simulations {
+            Node::simulate(root, config, None, &mut rng);
+        }
+    }
+
+    // A single select, rollout and backpropagate cycle, for custom search loops or stepping through one. Each call on
+    // a root that isn't terminal adds exactly one visit to it, and a terminal root is left alone.
+    pub fn run_one_simulation(root: &Rc<RefCell<Self>>) {
+        Node::run_one_simulation_with_rng(root, &SearchConfig::default(), &mut default_rng());
+    }
+
+    pub fn run_one_simulation_with_rng<R: Rng>(root: &Rc<RefCell<Self>>, config: &SearchConfig, rng: &mut R) {
+        if !root.borrow().is_terminal() {
+            Node::simulate(root, config, None, rng);
+        }
+    }
+
+    pub fn add_dirichlet_noise(root: &Rc<RefCell<Self>>, alpha: f64, epsilon: f64) {
+        Node::add_dirichlet_noise_with_rng(root, alpha, epsilon, &mut default_rng());
+    }
+
+    // Draws the noise from `rng`, so a seeded search with noise at the root is reproducible
+    pub fn add_dirichlet_noise_with_rng<R: Rng>(root: &Rc<RefCell<Self>>, alpha: f64, epsilon: f64, rng: &mut R) {
+        let mut root = root.borrow_mut();
+        let actions = root.state.get_legal_actions();
+        let action_probs = root.action_probs();
+        // The noise is handed out in move order rather than the map's order, which changes from run to run
+        let actions: Vec<State::Action> = actions.into_iter().filter(|action| action_probs.contains_key(action)).collect();
+        if actions.len() < 2 {
+            return;
+        }
+        let dirichlet = Dirichlet::new_with_size(alpha, actions.len()).expect("Invalid Dirichlet parameters");
+        let noise = dirichlet.sample(rng);
+        for (action, noise) in actions.iter().zip(noise) {
+            let prob = action_probs.get_mut(action).unwrap();
+            *prob = (1. - epsilon) * *prob + epsilon * noise;
+        }
+    }
+
+    pub fn action_distribution(root: &Rc<RefCell<Self>>) -> Vec<(State::Action, f64)> {
+        let root = root.borrow();
+        let total_visits: i32 = root.children.iter().map(|child| child.borrow().visit_count).sum();
+        if total_visits == 0 {
+            return Vec::new();
+        }
+        root.children.iter()
+            .filter_map(|child| {
+                let child = child.borrow();
+                child.parent_action.map(|action| (action, child.visit_count as f64 / total_visits as f64))
+            })
+            .collect()
+    }
+
+    pub fn child_stats(root: &Rc<RefCell<Self>>) -> Vec<ChildStat<State::Action>> {
+        let root = root.borrow();
+        let player = root.state.get_player_index();
+        root.children.iter()
+            .filter_map(|child| {
+                let child = child.borrow();
+                child.parent_action.map(|action| ChildStat {
+                    action,
+                    visit_count: child.visit_count,
+                    mean_value: mean_value(&child.total_values, player, child.visit_count).unwrap_or(0.),
+                    prior: root.action_probs.as_ref().and_then(|probs| probs.get(&action).copied()).unwrap_or(0.),
+                })
+            })
+            .collect()
+    }
+
+    pub fn principal_variation(root: &Rc<RefCell<Self>>) -> Vec<State::Action> {
+        let mut variation = Vec::new();
+        let mut current_node = Rc::clone(root);
+        loop {
+            let next_node = current_node.borrow().children.iter().max_by_key(|child| child.borrow().visit_count).map(Rc::clone);
+            match next_node {
+                Some(child) => {
+                    variation.extend(child.borrow().parent_action);
+                    current_node = child;
+                },
+                None => break,
+            }
         }
+        variation
+    }
+
+    // The chances of the player to move at the root after a search, in [0, 1]: value() mapped from [-1, 1] the way
+    // selection_score() maps values, so 1 is a sure win, 0 a sure loss and a draw counts as half a win. As a mean
+    // over every simulation, including those spent ruling out bad moves, it understates a clear win early on and
+    // sharpens as the search settles. 0.5 before any search.
+    pub fn win_probability(root: &Rc<RefCell<Self>>) -> f64 {
+        let root = root.borrow();
+        if root.visit_count == 0 { 0.5 } else { ((root.value() + 1.) / 2.).clamp(0., 1.) }
+    }
+
+    // How many moves the player to move needs to force a win, like "mate in 3", if the tree already proves one:
+    // at each of their turns some move leads on to the win, and at each of the opponent's every legal reply does
+    // too, either in the tree or by letting them win at once. The count is along the shortest such line the
+    // opponent can't lengthen. A win the search only thinks likely, because it hasn't looked at every defence,
+    // isn't reported.
+    pub fn moves_to_win(root: &Rc<RefCell<Self>>) -> Option<usize> {
+        let (player, terminal) = {
+            let root = root.borrow();
+            (root.state.get_player_turn(), root.is_terminal())
+        };
+        if terminal { None } else { Self::win_distance(root, player) }
+    }
 
-        // Select the action of the child with the highest visit count
-        root.borrow().children.iter().max_by_key(|child| child.borrow().visit_count)
-            .and_then(|child| child.borrow().parent_action).unwrap()
+    // Walked with an explicit stack rather than recursion, since a proof can run as deep as the game is long
+    fn win_distance(root: &Rc<RefCell<Self>>, player: i32) -> Option<usize> {
+        // Each node on the stack with the distances of the children walked so far
+        let mut stack = vec![(Rc::clone(root), Vec::<Option<usize>>::new())];
+        loop {
+            let (node, distances) = stack.last().unwrap();
+            let next_child = {
+                let node = node.borrow();
+                // Where every child has to lead to the win, one that doesn't settles it
+                let all_needed = !node.chance_weights.is_empty() || node.state.get_player_turn() != player;
+                if node.is_terminal() || (all_needed && distances.last() == Some(&None)) {
+                    None
+                } else {
+                    node.children.get(distances.len()).map(Rc::clone)
+                }
+            };
+            if let Some(child) = next_child {
+                stack.push((child, Vec::new()));
+                continue;
+            }
+
+            let (node, distances) = stack.pop().unwrap();
+            let distance = node.borrow().win_distance_from(&distances, player);
+            match stack.last_mut() {
+                Some((_, parent_distances)) => parent_distances.push(distance),
+                None => return distance,
+            }
+        }
+    }
+
+    // win_distance() of this node given those of its children, in order, up to the first that fails if all of them
+    // have to lead to the win
+    fn win_distance_from(&self, distances: &[Option<usize>], player: i32) -> Option<usize> {
+        if self.is_terminal() {
+            return if self.state.status() == GameStatus::Win(player) { Some(0) } else { None };
+        }
+        if self.children.is_empty() {
+            return None;
+        }
+        if self.chance_weights.is_empty() && self.state.get_player_turn() == player {
+            distances.iter().flatten().min().map(|distance| distance + 1)
+        } else {
+            let longest = distances.iter().try_fold(0, |longest, &distance| distance.map(|distance| longest.max(distance)))?;
+            if !self.chance_weights.is_empty() {
+                return Some(longest);
+            }
+            // Replies left out of the tree by prune_losing_moves or a near-zero prior still count if they lose at once
+            let expanded: Vec<State::Action> = self.children.iter().filter_map(|child| child.borrow().parent_action).collect();
+            self.state.get_legal_actions().into_iter()
+                .filter(|action| !expanded.contains(action))
+                .try_fold(longest, |longest, action| {
+                    immediate_win_distance(&self.state.get_next_state(action), player).map(|distance| longest.max(distance))
+                })
+        }
+    }
+
+    // Number of nodes in the tree, including the root
+    pub fn tree_size(root: &Rc<RefCell<Self>>) -> usize {
+        root.borrow().subtree_size
+    }
+
+    pub fn to_dot(root: &Rc<RefCell<Self>>, max_depth: usize) -> String {
+        let mut dot = String::from("digraph mcts {\n");
+        let mut stack = vec![(Rc::clone(root), 0, 0)];
+        let mut next_id = 1;
+        while let Some((node_rc, id, depth)) = stack.pop() {
+            let node = node_rc.borrow();
+            writeln!(dot, "    n{} [label=\"N={}\\nQ={:.3}\"];", id, node.visit_count, node.value()).unwrap();
+            if depth >= max_depth {
+                continue;
+            }
+            // A chance node's edges are labelled with the outcome's probability, since no action leads to them
+            for (i, child) in node.children.iter().enumerate() {
+                let label = match child.borrow().parent_action {
+                    Some(action) => format!("{:?}", action),
+                    None => format!("p={:.2}", node.chance_weights[i]),
+                };
+                writeln!(dot, "    n{} -> n{} [label=\"{}\"];", id, next_id, label).unwrap();
+                stack.push((Rc::clone(child), next_id, depth + 1));
+                next_id += 1;
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn advance(root: &Rc<RefCell<Self>>, action: State::Action) -> Rc<RefCell<Self>> {
+        Node::advance_with_stats(root, action).0
+    }
+
+    // Like advance(), also reporting how many of the old root's visits carry over to the new one
+    pub fn advance_with_stats(root: &Rc<RefCell<Self>>, action: State::Action) -> (Rc<RefCell<Self>>, AdvanceStats) {
+        let mut root = root.borrow_mut();
+        let new_root = match root.children.iter().position(|child| child.borrow().parent_action == Some(action)) {
+            Some(index) => {
+                let child = root.children.swap_remove(index);
+                child.borrow_mut().parent = None;
+                root.subtree_size -= child.borrow().subtree_size;
+                child
+            },
+            None => Node::new(root.state.get_next_state(action), None, Some(action)),
+        };
+        let reused_visits = new_root.borrow().visit_count;
+        let stats = AdvanceStats { reused_visits, discarded_visits: root.visit_count - reused_visits };
+        (new_root, stats)
+    }
+
+    // Moves a chance node root on to the outcome that actually happened, keeping its subtree if it was searched.
+    // Search from the outcome rather than the chance node, which has no actions to choose between.
+    pub fn advance_to_outcome(root: &Rc<RefCell<Self>>, outcome: State) -> Rc<RefCell<Self>> {
+        let mut root = root.borrow_mut();
+        let key = outcome.hash_key();
+        match root.children.iter().position(|child| child.borrow().state.hash_key() == key) {
+            Some(index) => {
+                root.chance_weights.swap_remove(index);
+                let child = root.children.swap_remove(index);
+                child.borrow_mut().parent = None;
+                root.subtree_size -= child.borrow().subtree_size;
+                child
+            },
+            None => Node::new(outcome, None, None),
+        }
+    }
+
+    // Runs one select-rollout-backpropagate pass, returning whether it expanded a node
+    pub(crate) fn simulate<R: Rng>(root: &Rc<RefCell<Self>>, config: &SearchConfig, table: Option<&mut TranspositionTable>, rng: &mut R) -> bool {
+        let (leaf_node, expanded) = Node::select_node(root, config, table.as_deref(), rng);
+        let results = leaf_node.borrow().rollout(config, rng);
+        Node::backpropagate(&leaf_node, &results, table);
+        Node::enforce_node_budget(root, config);
+        expanded
+    }
+
+    // Descends to a leaf, then has the provider score it and set the priors it's expanded with
+    fn simulate_with_provider<R: Rng>(root: &Rc<RefCell<Self>>, config: &SearchConfig, provider: &dyn ValuePolicyProvider<State>, rng: &mut R) {
+        let mut current_node = Rc::clone(root);
+        loop {
+            if current_node.borrow().children.is_empty() {
+                break;
+            }
+            if !current_node.borrow().pending_actions.is_empty() {
+                current_node = Node::expand_next(&current_node);
+                break;
+            }
+            let next_node = current_node.borrow().next_child(config, None, rng);
+            current_node = next_node;
+        }
+
+        let mut results = if current_node.borrow().is_terminal() {
+            current_node.borrow().state.get_results()
+        } else {
+            let (value, priors) = provider.evaluate(&current_node.borrow().state);
+            // Priors already there (e.g. with noise added at the root) are kept
+            current_node.borrow_mut().action_probs.get_or_insert_with(|| priors.into_iter().collect());
+            Node::expand(&current_node, config);
+            vec![value, -value]
+        };
+        apply_komi(config, &mut results);
+        Node::backpropagate(&current_node, &results, None);
+        Node::enforce_node_budget(root, config);
+    }
+
+    pub(crate) fn select_final_action<R: Rng>(root: &Rc<RefCell<Self>>, config: &SearchConfig, rng: &mut R) -> Option<State::Action> {
+        let children = Node::child_stats(root);
+        final_action(&root.borrow().state, &children, config, rng)
+    }
+
+    fn child_visits(root: &Rc<RefCell<Self>>) -> Vec<(State::Action, i32)> {
+        root.borrow().children.iter()
+            .filter_map(|child| {
+                let child = child.borrow();
+                child.parent_action.map(|action| (action, child.visit_count))
+            })
+            .collect()
     }
 }
+
+impl<State: GameState + Clone + Send> Node<State> where State::Action: Send {
+    // Root-parallel search: each thread builds its own tree and the root visit counts are summed at the end
+    pub fn best_action_parallel(state: &State, n_simulations: i32, n_threads: usize) -> Option<State::Action> {
+        if state.is_terminal() {
+            return None;
+        }
+        let n_threads = n_threads.max(1);
+        let config = SearchConfig { n_simulations: (n_simulations + n_threads as i32 - 1) / n_threads as i32, ..SearchConfig::default() };
+
+        let mut visits: HashMap<State::Action, i32> = HashMap::new();
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..n_threads).map(|_| {
+                let state = state.clone();
+                let config = &config;
+                scope.spawn(move || {
+                    let root = Node::new(state, None, None);
+                    let mut rng = default_rng();
+                    for _ in 0..config.n_simulations {
+                        Node::simulate(&root, config, None, &mut rng);
+                    }
+                    Node::child_visits(&root)
+                })
+            }).collect();
+
+            for handle in handles {
+                for (action, count) in handle.join().expect("Search thread panicked") {
+                    *visits.entry(action).or_insert(0) += count;
+                }
+            }
+        });
+
+        visits.into_iter().max_by_key(|&(_, count)| count).map(|(action, _)| action)
+    }
+}
+
+// One node of a tree written by Node::save(), listed parents first, with the parent given by its position in the
+// list. A flat list keeps the nesting out of the file, so deep trees can't overflow the stack on either end.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedNode<State, Action> {
+    state: State,
+    parent: Option<usize>,
+    parent_action: Option<Action>,
+    visit_count: i32,
+    total_values: Vec<f64>,
+    action_probs: Option<Vec<(Action, f64)>>,
+    chance_weights: Vec<f64>,
+    pending_actions: Vec<Action>,
+}
+
+#[cfg(feature = "serde")]
+impl<State> Node<State>
+where
+    State: GameState + Clone + serde::Serialize + serde::de::DeserializeOwned,
+    State::Action: serde::Serialize + serde::de::DeserializeOwned,
+{
+    // Writes the tree below `root` to `path` as JSON: the states, visits, values and priors of every node, so a
+    // long analysis can be picked up again with load()
+    pub fn save(root: &Rc<RefCell<Self>>, path: &str) -> std::io::Result<()> {
+        let mut nodes = Vec::new();
+        let mut stack = vec![(Rc::clone(root), None)];
+        while let Some((node_rc, parent)) = stack.pop() {
+            let node = node_rc.borrow();
+            let index = nodes.len();
+            nodes.push(SavedNode {
+                state: node.state.clone(),
+                parent,
+                parent_action: node.parent_action,
+                visit_count: node.visit_count,
+                total_values: node.total_values.clone(),
+                action_probs: node.action_probs.as_ref().map(|probs| probs.iter().map(|(&action, &prob)| (action, prob)).collect()),
+                chance_weights: node.chance_weights.clone(),
+                pending_actions: node.pending_actions.clone(),
+            });
+            // Pushed in reverse, so the children are listed in their original order
+            stack.extend(node.children.iter().rev().map(|child| (Rc::clone(child), Some(index))));
+        }
+        let json = serde_json::to_string(&nodes).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        std::fs::write(path, json)
+    }
+
+    // Reads back a tree written by save(), rebuilding the parent links, as a new root
+    pub fn load(path: &str) -> std::io::Result<Rc<RefCell<Self>>> {
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let json = std::fs::read_to_string(path)?;
+        let saved: Vec<SavedNode<State, State::Action>> = serde_json::from_str(&json).map_err(|err| invalid(err.to_string()))?;
+
+        let mut nodes: Vec<Rc<RefCell<Self>>> = Vec::with_capacity(saved.len());
+        let mut parents = Vec::with_capacity(saved.len());
+        for (index, saved) in saved.into_iter().enumerate() {
+            let parent = match saved.parent {
+                None if index == 0 => None,
+                Some(parent) if index > 0 && parent < index => Some(parent),
+                _ => return Err(invalid(format!("node {} has parent {:?}, but only the first node is a root and parents come first", index, saved.parent))),
+            };
+            if saved.total_values.len() != saved.state.num_players() {
+                return Err(invalid(format!("node {} has {} values for {} players", index, saved.total_values.len(), saved.state.num_players())));
+            }
+            let node = Node::new(saved.state, parent.map(|parent| Rc::downgrade(&nodes[parent])), saved.parent_action);
+            {
+                let mut node = node.borrow_mut();
+                node.visit_count = saved.visit_count;
+                node.total_values = saved.total_values;
+                node.action_probs = saved.action_probs.map(|probs| probs.into_iter().collect());
+                node.chance_weights = saved.chance_weights;
+                node.pending_actions = saved.pending_actions;
+            }
+            if let Some(parent) = parent {
+                nodes[parent].borrow_mut().children.push(Rc::clone(&node));
+            }
+            nodes.push(node);
+            parents.push(parent);
+        }
+
+        // A chance node needs a weight for each outcome, and its outcomes aren't reached by a move
+        for (index, node) in nodes.iter().enumerate() {
+            let node = node.borrow();
+            if node.chance_weights.is_empty() {
+                continue;
+            }
+            if node.chance_weights.len() != node.children.len() {
+                return Err(invalid(format!("node {} has {} chance weights for {} children", index, node.chance_weights.len(), node.children.len())));
+            }
+            if node.children.iter().any(|child| child.borrow().parent_action.is_some()) {
+                return Err(invalid(format!("node {} is a chance node, but one of its children has a parent action", index)));
+            }
+        }
+
+        // Every node comes after its parent, so walking backwards adds up each subtree before its parent's
+        for (index, parent) in parents.iter().enumerate().rev() {
+            if let Some(parent) = *parent {
+                let size = nodes[index].borrow().subtree_size;
+                nodes[parent].borrow_mut().subtree_size += size;
+            }
+        }
+        nodes.first().cloned().ok_or_else(|| invalid("the file holds no nodes".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests;
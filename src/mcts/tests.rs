@@ -0,0 +1,1214 @@
+use super::*;
+use crate::arena::Arena;
+use crate::connect4;
+use crate::ttt;
+use std::collections::HashSet;
+
+// The ttt position after playing `moves` from the empty board
+fn ttt_after(moves: &[(usize, usize)]) -> ttt::State {
+    moves.iter().fold(ttt::State::new(), |state, &action| state.get_next_state(action))
+}
+
+// The connect4 position after dropping pieces into `columns` from the empty board
+fn connect4_after(columns: &[usize]) -> connect4::State {
+    columns.iter().fold(connect4::State::new(), |state, &col| state.get_next_state(col))
+}
+
+// The child of `node` reached by `action`, if it's in the tree
+fn child<State: GameState + Clone>(node: &Rc<RefCell<Node<State>>>, action: State::Action) -> Option<Rc<RefCell<Node<State>>>> {
+    node.borrow().children.iter().find(|child| child.borrow().parent_action == Some(action)).map(Rc::clone)
+}
+
+// The action of the most visited child of `node`, the last of equals like the final move choice
+fn most_visited<State: GameState + Clone>(node: &Rc<RefCell<Node<State>>>) -> Option<State::Action> {
+    node.borrow().children.iter().max_by_key(|child| child.borrow().visit_count).and_then(|child| child.borrow().parent_action)
+}
+
+#[test]
+fn transpositions_share_a_table_entry() {
+    // X takes (0, 1) and (1, 0) around O's (2, 2) in either order, so each search reaches the same board
+    let paths = [[(0, 1), (2, 2), (1, 0)], [(1, 0), (2, 2), (0, 1)]];
+    let config = SearchConfig::default();
+    let mut table = TranspositionTable::new();
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut nodes = Vec::new();
+    for path in paths {
+        let root = Node::new(ttt_after(&[(1, 1), (0, 0), path[0], path[1]]), None, None);
+        for _ in 0..300 {
+            Node::simulate(&root, &config, Some(&mut table), &mut rng);
+        }
+        nodes.push((Rc::clone(&root), child(&root, path[2]).expect("transposed move not searched")));
+    }
+
+    let (first, second) = (nodes[0].1.borrow(), nodes[1].1.borrow());
+    assert_eq!(first.state.hash_key(), second.state.hash_key());
+    assert!(first.visit_count > 0 && second.visit_count > 0);
+
+    // Both nodes read the same pooled statistics, which cover the visits through either path
+    let key = first.state.canonical_key();
+    assert!(table.visit_count(key) >= first.visit_count + second.visit_count);
+    let player = nodes[0].0.borrow().state.get_player_index();
+    assert_eq!(first.stats(Some(&table), player), second.stats(Some(&table), player));
+    assert_eq!(first.stats(Some(&table), player).0, table.visit_count(key));
+}
+
+#[test]
+fn seeded_searches_repeat_their_moves() {
+    let play_game = |seed: u64| {
+        let mut state = ttt::State::new();
+        let mut actions = Vec::new();
+        while let Some(action) = Node::best_action_seeded(&Node::new(state.clone(), None, None), 200, seed) {
+            actions.push(action);
+            state = state.get_next_state(action);
+        }
+        actions
+    };
+    for seed in 0..3 {
+        assert_eq!(play_game(seed), play_game(seed));
+    }
+}
+
+#[test]
+fn zero_temperature_picks_the_most_visited_move() {
+    for seed in 0..5 {
+        let root = Node::new(ttt::State::new(), None, None);
+        let config = SearchConfig { n_simulations: 200, temperature: 0., ..SearchConfig::default() };
+        let action = Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(action, most_visited(&root));
+
+        // Sampling again from the same tree at temperature 0 never strays from the argmax
+        for draw in 0..20 {
+            assert_eq!(Node::select_final_action(&root, &config, &mut StdRng::seed_from_u64(draw)), action);
+        }
+    }
+}
+
+// A three-player game of two moves. Player 0 picks a branch, then player 1 settles it. In branch 0 player 1
+// chooses whether player 0 or player 1 wins, and in branch 1 whether nobody does or player 2 does. Player 1 takes
+// the win in branch 0 and the draw in branch 1, so branch 1 is better for player 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Kingmaker {
+    branch: Option<usize>,
+    outcome: Option<usize>,
+}
+
+impl GameState for Kingmaker {
+    type Action = usize;
+
+    fn status(&self) -> GameStatus {
+        if self.outcome.is_some() { GameStatus::Draw } else { GameStatus::InProgress }
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.get_player_index() as i32
+    }
+
+    fn get_legal_actions(&self) -> Vec<usize> {
+        if self.outcome.is_some() { Vec::new() } else { vec![0, 1] }
+    }
+
+    fn get_policy(&self, actions: &[usize]) -> Vec<f64> {
+        vec![1. / actions.len() as f64; actions.len()]
+    }
+
+    fn get_next_state(&self, action: usize) -> Kingmaker {
+        match self.branch {
+            None => Kingmaker { branch: Some(action), outcome: None },
+            Some(_) => Kingmaker { outcome: Some(action), ..*self },
+        }
+    }
+
+    fn hash_key(&self) -> u64 {
+        (self.branch.map_or(0, |branch| branch + 1) * 3 + self.outcome.map_or(0, |outcome| outcome + 1)) as u64
+    }
+
+    fn num_players(&self) -> usize {
+        3
+    }
+
+    fn get_player_index(&self) -> usize {
+        if self.branch.is_none() { 0 } else { 1 }
+    }
+
+    fn get_results(&self) -> Vec<f64> {
+        match (self.branch, self.outcome) {
+            (Some(0), Some(0)) => vec![1., -1., -1.],
+            (Some(0), Some(_)) => vec![-1., 1., -1.],
+            (Some(_), Some(0)) => vec![0., 0., 0.],
+            (Some(_), Some(_)) => vec![-1., -1., 1.],
+            _ => vec![0., 0., 0.],
+        }
+    }
+}
+
+#[test]
+fn each_player_is_credited_with_their_own_value() {
+    let root = Node::new(Kingmaker { branch: None, outcome: None }, None, None);
+    let config = SearchConfig { n_simulations: 400, ..SearchConfig::default() };
+    assert_eq!(Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0)), Some(1));
+
+    // Player 1 takes the win in branch 0 and avoids player 2's win in branch 1
+    assert_eq!(most_visited(&child(&root, 0).unwrap()), Some(1));
+    assert_eq!(most_visited(&child(&root, 1).unwrap()), Some(0));
+
+    // Player 2 loses every game in branch 0, whoever player 1 hands the win to
+    let branch = child(&root, 0).unwrap();
+    let branch = branch.borrow();
+    assert_eq!(branch.total_values().len(), 3);
+    assert_eq!(branch.total_values()[2], -branch.visits() as f64);
+}
+
+#[test]
+fn two_player_values_stay_zero_sum() {
+    let root = Node::new(ttt::State::new(), None, None);
+    let config = SearchConfig { n_simulations: 300, ..SearchConfig::default() };
+    Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0));
+
+    let mut stack = vec![Rc::clone(&root)];
+    while let Some(node) = stack.pop() {
+        let node = node.borrow();
+        assert_eq!(node.total_values().len(), 2);
+        assert_eq!(node.total_values()[0], -node.total_values()[1]);
+        // The mean for the player to move at a child is the negation of its parent's view of it
+        if node.visits() > 0 && node.parent.is_some() {
+            assert_eq!(node.value(), -node.value_for_parent().unwrap());
+        }
+        stack.extend(node.children.iter().map(Rc::clone));
+    }
+}
+
+#[test]
+fn ucb1_visits_every_child_before_revisiting_one() {
+    let root = Node::new(ttt::State::new(), None, None);
+    let config = SearchConfig { selection_policy: SelectionPolicy::Ucb1 { c: 1.4 }, ..SearchConfig::default() };
+    let mut rng = StdRng::seed_from_u64(0);
+    for simulations in 1..=9 {
+        Node::simulate(&root, &config, None, &mut rng);
+        let visits: Vec<i32> = root.borrow().children.iter().map(|child| child.borrow().visits()).collect();
+        assert_eq!(visits.iter().filter(|&&count| count == 1).count(), simulations);
+        assert!(visits.iter().all(|&count| count <= 1));
+    }
+}
+
+// Players take one or two counters in turn from a pile, and whoever takes the last one wins. evaluate() always
+// says the first player is slightly ahead, so it's easy to tell a heuristic score from a played-out one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Race {
+    remaining: u32,
+    player: i32,
+}
+
+const RACE_EVALUATION: f64 = 0.25;
+
+impl GameState for Race {
+    type Action = u32;
+
+    fn status(&self) -> GameStatus {
+        if self.remaining == 0 { GameStatus::Win(-self.player) } else { GameStatus::InProgress }
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.player
+    }
+
+    fn get_legal_actions(&self) -> Vec<u32> {
+        (1..=self.remaining.min(2)).collect()
+    }
+
+    fn get_policy(&self, actions: &[u32]) -> Vec<f64> {
+        vec![1. / actions.len() as f64; actions.len()]
+    }
+
+    fn get_next_state(&self, action: u32) -> Race {
+        Race { remaining: self.remaining - action, player: -self.player }
+    }
+
+    fn hash_key(&self) -> u64 {
+        (self.remaining as u64) << 1 | (self.player == 1) as u64
+    }
+
+    fn evaluate(&self) -> f64 {
+        RACE_EVALUATION
+    }
+}
+
+#[test]
+fn zero_rollout_depth_scores_leaves_with_the_heuristic() {
+    let root = Node::new(Race { remaining: 30, player: 1 }, None, None);
+    assert!(Node::best_action_with_depth(&root, 20, 0).is_some());
+
+    // No leaf was close enough to the end to be terminal, so every value came straight from evaluate()
+    let root = root.borrow();
+    assert_eq!(root.visits(), 20);
+    assert_eq!(root.total_values(), &[20. * RACE_EVALUATION, -20. * RACE_EVALUATION]);
+}
+
+#[test]
+fn dirichlet_noise_keeps_the_root_prior_normalized() {
+    let root = Node::new(crate::connect4::State::new(), None, None);
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..50 {
+        Node::simulate(&root, &SearchConfig::default(), None, &mut rng);
+    }
+    let before = root.borrow_mut().action_probs().clone();
+    let deeper: Vec<_> = root.borrow().children.iter().map(|child| child.borrow().action_probs.clone()).collect();
+
+    Node::add_dirichlet_noise_with_rng(&root, 0.3, 0.25, &mut StdRng::seed_from_u64(1));
+    let after = root.borrow_mut().action_probs().clone();
+    assert!((after.values().sum::<f64>() - 1.).abs() < 1e-9);
+    assert!(after.iter().any(|(action, prob)| (prob - before[action]).abs() > 1e-6));
+
+    // Only the root's prior changes
+    let deeper_after: Vec<_> = root.borrow().children.iter().map(|child| child.borrow().action_probs.clone()).collect();
+    assert_eq!(deeper, deeper_after);
+
+    // The same seed mixes in the same noise, on a fresh root with its own map
+    let other = Node::new(crate::connect4::State::new(), None, None);
+    Node::add_dirichlet_noise_with_rng(&other, 0.3, 0.25, &mut StdRng::seed_from_u64(1));
+    assert_eq!(*other.borrow_mut().action_probs(), after);
+}
+
+#[test]
+fn parallel_search_agrees_with_a_single_thread() {
+    // O has to block the top row
+    let state = ttt_after(&[(0, 0), (1, 1), (0, 1)]);
+    let single = Node::best_action_seeded(&Node::new(state.clone(), None, None), 2000, 0);
+    assert_eq!(single, Some((0, 2)));
+    assert_eq!(Node::best_action_parallel(&state, 2000, 4), single);
+
+    let action = Node::best_action_parallel(&ttt::State::new(), 400, 4).unwrap();
+    assert!(ttt::State::new().get_legal_actions().contains(&action));
+    assert_eq!(Node::best_action_parallel(&ttt_after(&[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]), 100, 2), None);
+}
+
+#[test]
+fn searching_a_finished_game_returns_none() {
+    let won = ttt_after(&[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]);
+    let root = Node::new(won, None, None);
+    assert_eq!(Node::best_action(&root, 100), None);
+    assert_eq!(Node::search(&root, &SearchConfig::default()).action, None);
+    assert_eq!(Node::best_action_timed(&root, Duration::from_millis(10)), None);
+    assert_eq!(root.borrow().visits(), 0);
+}
+
+#[test]
+fn reverting_a_virtual_loss_restores_the_stats() {
+    let root = Node::new(ttt::State::new(), None, None);
+    Node::best_action_seeded(&root, 200, 0);
+    let node = child(&root, (1, 1)).unwrap();
+    let before = (node.borrow().parent_stats(None), node.borrow().visits(), node.borrow().total_values().to_vec());
+
+    node.borrow_mut().apply_virtual_loss();
+    let (visit_count, value) = node.borrow().parent_stats(None);
+    assert_eq!(visit_count, before.0.0 + 1);
+    assert!(value.unwrap() < before.0.1.unwrap());
+
+    node.borrow_mut().revert_virtual_loss();
+    assert_eq!((node.borrow().parent_stats(None), node.borrow().visits(), node.borrow().total_values().to_vec()), before);
+}
+
+#[test]
+#[should_panic(expected = "never applied")]
+fn reverting_a_virtual_loss_that_was_never_applied_panics() {
+    Node::new(ttt::State::new(), None, None).borrow_mut().revert_virtual_loss();
+}
+
+// A game that never ends but runs out of moves: each move uses one of `moves_left`, and status() never reports
+// a result, as a buggy game might
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Stalled {
+    moves_left: u32,
+}
+
+impl GameState for Stalled {
+    type Action = u32;
+
+    fn status(&self) -> GameStatus {
+        GameStatus::InProgress
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        if self.moves_left.is_multiple_of(2) { 1 } else { -1 }
+    }
+
+    fn get_legal_actions(&self) -> Vec<u32> {
+        if self.moves_left > 0 { vec![0, 1] } else { Vec::new() }
+    }
+
+    fn get_policy(&self, actions: &[u32]) -> Vec<f64> {
+        vec![1. / actions.len() as f64; actions.len()]
+    }
+
+    fn get_next_state(&self, _action: u32) -> Stalled {
+        Stalled { moves_left: self.moves_left - 1 }
+    }
+
+    fn hash_key(&self) -> u64 {
+        self.moves_left as u64
+    }
+}
+
+#[test]
+fn positions_without_moves_are_scored_as_they_stand() {
+    let root = Node::new(Stalled { moves_left: 3 }, None, None);
+    assert!(Node::best_action(&root, 100).is_some());
+    assert_eq!(root.borrow().visits(), 100);
+    assert_eq!(root.borrow().total_values(), &[0., 0.]);
+
+    let stuck = Node::new(Stalled { moves_left: 0 }, None, None);
+    assert_eq!(Node::best_action(&stuck, 10), None);
+    assert_eq!(stuck.borrow().visits(), 10);
+    assert_eq!(Arena::new(Stalled { moves_left: 3 }).best_action(100).map(|action| action < 2), Some(true));
+
+    // A full connect4 board without a line is a draw, with nothing to search
+    let full_board = ["OOXXOOX", "OOXXOOX", "XXOOXXO", "OOXXOOX", "XXOOXXO", "XXOOXXO"];
+    let full = Node::new(crate::connect4::State::from_board(&full_board, 'X').unwrap(), None, None);
+    assert_eq!(Node::best_action(&full, 10), None);
+}
+
+#[test]
+fn rollouts_accept_unnormalized_weights() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let draws = 10_000;
+    let seconds = (0..draws).filter(|_| RandomRollout.choose(&[2., 3.], &mut rng) == Some(1)).count();
+    assert!((seconds as f64 / draws as f64 - 0.6).abs() < 0.02);
+
+    // Weights that can't be sampled from fall back to a uniform choice
+    assert!(RandomRollout.choose(&[0., 0.], &mut rng).is_some_and(|i| i < 2));
+}
+
+#[test]
+fn first_play_urgency_decides_between_visited_and_unvisited_children() {
+    // The corner has been visited once and drawn. Every other move is unvisited.
+    let root = Node::new(ttt::State::new(), None, None);
+    Node::expand(&root, &SearchConfig::default());
+    root.borrow_mut().visit_count = 1;
+    child(&root, (0, 0)).unwrap().borrow_mut().visit_count = 1;
+
+    let first_choice = |first_play_urgency: f64| {
+        let config = SearchConfig { first_play_urgency, ..SearchConfig::default() };
+        root.borrow().best_child(&config, None).borrow().parent_action
+    };
+    // Pessimistic urgency sticks with the draw, optimistic urgency tries a new move, the first of equals
+    assert_eq!(first_choice(-1.), Some((0, 0)));
+    assert_eq!(first_choice(1.), Some((0, 1)));
+}
+
+#[test]
+fn quick_win_needs_a_single_simulation() {
+    let config = SearchConfig { n_simulations: 1, quick_win: true, ..SearchConfig::default() };
+
+    // X has three in column 0
+    let root = Node::new(connect4_after(&[0, 1, 0, 1, 0, 1]), None, None);
+    assert_eq!(Node::best_action_with_config(&root, &config), Some(0));
+    assert_eq!(root.borrow().visits(), 0);
+
+    // X has three along the bottom with both ends open, so O loses whatever it plays and blocks one end
+    let root = Node::new(connect4_after(&[1, 6, 2, 6, 3]), None, None);
+    let action = Node::best_action_with_config(&root, &config);
+    assert!(action == Some(0) || action == Some(4), "played {:?}", action);
+    assert_eq!(root.borrow().visits(), 0);
+}
+
+#[test]
+fn a_deep_chain_drops_without_overflowing_the_stack() {
+    // A million nodes deep, far past what a recursive drop could unwind on a test thread's stack
+    let depth = 1_000_000;
+    let root = Node::new(Race { remaining: depth, player: 1 }, None, None);
+    let mut node = Rc::clone(&root);
+    for remaining in (0..depth).rev() {
+        let next = Node::new(Race { remaining, player: 1 }, Some(Rc::downgrade(&node)), Some(1));
+        node.borrow_mut().children.push(Rc::clone(&next));
+        node = next;
+    }
+    drop(node);
+    drop(root);
+}
+
+#[test]
+fn each_simulation_adds_one_visit() {
+    let root = Node::new(ttt::State::new(), None, None);
+    for visits in 1..=50 {
+        Node::run_one_simulation(&root);
+        assert_eq!(root.borrow().visits(), visits);
+    }
+
+    // A finished game has nothing to simulate
+    let won = Node::new(ttt_after(&[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]), None, None);
+    Node::run_one_simulation(&won);
+    assert_eq!(won.borrow().visits(), 0);
+}
+
+#[test]
+fn komi_moves_backed_up_values_towards_the_second_player() {
+    let config = SearchConfig { komi: 0.25, ..SearchConfig::default() };
+    let mut rng = StdRng::seed_from_u64(0);
+    // X's only move, in the last square, draws
+    let last_move = ttt_after(&[(0, 0), (0, 1), (0, 2), (1, 1), (1, 0), (1, 2), (2, 1), (2, 0)]);
+    assert_eq!(rollout(&last_move.get_next_state((2, 2)), &config, &mut rng), vec![-0.25, 0.25]);
+    let won = ttt_after(&[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]);
+    assert_eq!(rollout(&won, &config, &mut rng), vec![0.75, -0.75]);
+
+    let root = Node::new(last_move.clone(), None, None);
+    for _ in 0..10 {
+        Node::run_one_simulation_with_rng(&root, &config, &mut rng);
+    }
+    assert_eq!(root.borrow().total_values(), &[-2.5, 2.5]);
+    // Without komi the same draws back up as nothing
+    let root = Node::new(last_move, None, None);
+    for _ in 0..10 {
+        Node::run_one_simulation_with_rng(&root, &SearchConfig::default(), &mut rng);
+    }
+    assert_eq!(root.borrow().total_values(), &[0., 0.]);
+}
+
+#[test]
+fn expand_one_adds_a_child_per_visit_in_prior_order() {
+    let state = connect4::State::new();
+    let actions = state.get_legal_actions();
+    let priors = state.get_policy(&actions);
+    let mut by_prior: Vec<usize> = (0..actions.len()).collect();
+    by_prior.sort_by(|&a, &b| compare_scores(priors[b], priors[a]));
+    let by_prior: Vec<usize> = by_prior.into_iter().map(|i| actions[i]).collect();
+
+    let config = SearchConfig { expand_one: true, ..SearchConfig::default() };
+    let root = Node::new(state.clone(), None, None);
+    let mut rng = StdRng::seed_from_u64(0);
+    for visits in 1..=actions.len() {
+        Node::run_one_simulation_with_rng(&root, &config, &mut rng);
+        let children: Vec<usize> = root.borrow().children.iter().filter_map(|child| child.borrow().parent_action).collect();
+        assert_eq!(children, by_prior[..visits]);
+        // Each visit only adds the new child, without expanding it in turn
+        assert_eq!(count_nodes(&root), visits + 1);
+    }
+
+    // Once every move is a child, visits go back to descending into them
+    Node::run_one_simulation_with_rng(&root, &config, &mut rng);
+    assert_eq!(root.borrow().children.len(), actions.len());
+    assert_eq!(count_nodes(&root), actions.len() + 2);
+
+    // Without it, the first visit adds every move at once
+    let root = Node::new(state, None, None);
+    Node::run_one_simulation_with_rng(&root, &SearchConfig::default(), &mut rng);
+    assert_eq!(root.borrow().children.len(), actions.len());
+}
+
+#[test]
+fn moves_to_win_counts_the_shortest_forced_line() {
+    let config = SearchConfig { n_simulations: 2000, ..SearchConfig::default() };
+    let searched = |remaining: u32| {
+        let root = Node::new(Race { remaining, player: 1 }, None, None);
+        Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0));
+        Node::moves_to_win(&root)
+    };
+    // Taking the last counter, or leaving the opponent a multiple of three, wins however they reply
+    assert_eq!(searched(2), Some(1));
+    assert_eq!(searched(4), Some(2));
+    assert_eq!(searched(7), Some(3));
+    // Whatever the mover takes, the opponent can leave them a multiple of three
+    assert_eq!(searched(6), None);
+
+    // Nothing is proved before a search, and there's nothing left to win once the game is over
+    assert_eq!(Node::moves_to_win(&Node::new(Race { remaining: 4, player: 1 }, None, None)), None);
+    assert_eq!(Node::moves_to_win(&Node::new(Race { remaining: 0, player: 1 }, None, None)), None);
+}
+
+// A single move at a time down a corridor, with the players taking turns, and the first player wins at its end
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Corridor {
+    steps_left: u32,
+}
+
+impl GameState for Corridor {
+    type Action = u32;
+
+    fn status(&self) -> GameStatus {
+        if self.steps_left == 0 { GameStatus::Win(1) } else { GameStatus::InProgress }
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        if self.steps_left.is_multiple_of(2) { 1 } else { -1 }
+    }
+
+    fn get_legal_actions(&self) -> Vec<u32> {
+        if self.steps_left > 0 { vec![0] } else { Vec::new() }
+    }
+
+    fn get_policy(&self, actions: &[u32]) -> Vec<f64> {
+        vec![1.; actions.len()]
+    }
+
+    fn get_next_state(&self, _action: u32) -> Corridor {
+        Corridor { steps_left: self.steps_left - 1 }
+    }
+
+    fn hash_key(&self) -> u64 {
+        self.steps_left as u64
+    }
+}
+
+#[test]
+fn win_probability_reads_the_root_for_the_player_to_move() {
+    let searched = |state: Corridor| {
+        let root = Node::new(state, None, None);
+        Node::best_action_with_rng(&root, &SearchConfig { n_simulations: 50, ..SearchConfig::default() }, &mut StdRng::seed_from_u64(0));
+        Node::win_probability(&root)
+    };
+    // The first player wins down the corridor whatever happens, so it's a sure win for them and a sure loss for
+    // the second
+    assert_eq!(searched(Corridor { steps_left: 4 }), 1.);
+    assert_eq!(searched(Corridor { steps_left: 5 }), 0.);
+    assert_eq!(Node::win_probability(&Node::new(Corridor { steps_left: 4 }, None, None)), 0.5);
+
+    // A forced win that's not the only line still comes out close to sure once the search has settled on it
+    let root = Node::new(Race { remaining: 4, player: 1 }, None, None);
+    Node::best_action_with_rng(&root, &SearchConfig { n_simulations: 2000, ..SearchConfig::default() }, &mut StdRng::seed_from_u64(0));
+    let probability = Node::win_probability(&root);
+    assert!(probability > 0.9 && probability <= 1., "{}", probability);
+}
+
+#[test]
+fn moves_to_win_follows_a_deep_proof_without_overflowing_the_stack() {
+    // A million nodes deep, far past what a recursive walk could descend on a test thread's stack
+    let depth = 1_000_000;
+    let root = Node::new(Corridor { steps_left: depth }, None, None);
+    let mut node = Rc::clone(&root);
+    for steps_left in (0..depth).rev() {
+        let next = Node::new(Corridor { steps_left }, Some(Rc::downgrade(&node)), Some(0));
+        node.borrow_mut().children.push(Rc::clone(&next));
+        node = next;
+    }
+    drop(node);
+    assert_eq!(Node::moves_to_win(&root), Some(depth as usize / 2));
+}
+
+#[test]
+fn greedy_rollouts_take_a_winning_drop() {
+    // X to move with three in column 0, and O with three in column 1 if X lets it through
+    let state = connect4_after(&[0, 1, 0, 1, 0, 1]);
+    let actions = state.get_legal_actions();
+    let weights = state.get_rollout_policy(&actions);
+    assert_eq!(GreedyRollout.choose(&weights, &mut StdRng::seed_from_u64(0)), Some(0));
+
+    let config = SearchConfig { rollout_strategy: Arc::new(GreedyRollout), ..SearchConfig::default() };
+    for seed in 0..5 {
+        assert_eq!(rollout(&state, &config, &mut StdRng::seed_from_u64(seed)), vec![1., -1.]);
+    }
+}
+
+// Tic-tac-toe with a policy that gives its first legal move a NaN prior, as a buggy network might
+#[derive(Clone)]
+struct NanPrior(ttt::State);
+
+impl GameState for NanPrior {
+    type Action = (usize, usize);
+
+    fn status(&self) -> GameStatus {
+        self.0.status()
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.0.get_player_turn()
+    }
+
+    fn get_legal_actions(&self) -> Vec<(usize, usize)> {
+        self.0.get_legal_actions()
+    }
+
+    fn get_policy(&self, actions: &[(usize, usize)]) -> Vec<f64> {
+        let mut policy = self.0.get_policy(actions);
+        if let Some(prior) = policy.first_mut() {
+            *prior = f64::NAN;
+        }
+        policy
+    }
+
+    fn get_next_state(&self, action: (usize, usize)) -> NanPrior {
+        NanPrior(self.0.get_next_state(action))
+    }
+
+    fn hash_key(&self) -> u64 {
+        self.0.hash_key()
+    }
+}
+
+#[test]
+fn nan_priors_are_never_preferred() {
+    assert_eq!(GreedyRollout.choose(&[f64::NAN, 0.2, 0.5], &mut StdRng::seed_from_u64(0)), Some(2));
+    assert_eq!(GreedyRollout.choose(&[0.5, f64::NAN, 0.5], &mut StdRng::seed_from_u64(0)), Some(0));
+
+    // X threatens (1, 2) and (2, 0) at once, so every move loses and the prior picks one
+    let fork = NanPrior(ttt_after(&[(0, 0), (0, 1), (1, 1), (2, 2), (1, 0)]));
+    let nan_action = fork.get_legal_actions()[0];
+    assert!(decided_action(&fork).is_some_and(|action| action != nan_action));
+
+    // The search runs to the end rather than panicking or getting stuck on the NaN child
+    let config = SearchConfig { n_simulations: 300, rollout_strategy: Arc::new(GreedyRollout), ..SearchConfig::default() };
+    let root = Node::new(NanPrior(ttt::State::new()), None, None);
+    let action = Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0));
+    assert!(action.is_some_and(|action| ttt::State::new().get_legal_actions().contains(&action)));
+    assert_eq!(root.borrow().visits(), 300);
+}
+
+#[test]
+fn opening_moves_are_sampled_and_later_moves_are_not() {
+    let children: Vec<ChildStat<(usize, usize)>> = [((1, 1), 60), ((0, 0), 30), ((2, 2), 10)].into_iter()
+        .map(|(action, visit_count)| ChildStat { action, visit_count, mean_value: 0., prior: 1. / 3. })
+        .collect();
+    let config = SearchConfig { temperature: 0., opening_moves: 2, ..SearchConfig::default() };
+    let picks = |state: &ttt::State| {
+        (0..50).map(|draw| final_action(state, &children, &config, &mut StdRng::seed_from_u64(draw)).unwrap()).collect::<HashSet<_>>()
+    };
+
+    // The first two moves of the game vary, and the third is the most visited every time
+    assert!(picks(&ttt::State::new()).len() > 1);
+    assert!(picks(&ttt_after(&[(0, 1)])).len() > 1);
+    assert_eq!(picks(&ttt_after(&[(0, 1), (1, 0)])), HashSet::from([(1, 1)]));
+}
+
+thread_local! {
+    static LEGAL_ACTION_CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    static POLICY_CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// ttt with a count of the get_legal_actions() and get_policy() calls made on this thread
+#[derive(Clone)]
+struct CountedTtt(ttt::State);
+
+impl GameState for CountedTtt {
+    type Action = (usize, usize);
+
+    fn status(&self) -> GameStatus {
+        self.0.status()
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.0.get_player_turn()
+    }
+
+    fn get_legal_actions(&self) -> Vec<(usize, usize)> {
+        LEGAL_ACTION_CALLS.with(|calls| calls.set(calls.get() + 1));
+        self.0.get_legal_actions()
+    }
+
+    fn get_policy(&self, actions: &[(usize, usize)]) -> Vec<f64> {
+        POLICY_CALLS.with(|calls| calls.set(calls.get() + 1));
+        self.0.get_policy(actions)
+    }
+
+    fn get_next_state(&self, action: (usize, usize)) -> CountedTtt {
+        CountedTtt(self.0.get_next_state(action))
+    }
+
+    fn hash_key(&self) -> u64 {
+        self.0.hash_key()
+    }
+}
+
+#[test]
+fn expanding_a_node_asks_for_its_moves_once() {
+    // Leaves are scored without a playout, which would ask for moves of its own
+    let config = SearchConfig { n_simulations: 500, max_rollout_depth: Some(0), ..SearchConfig::default() };
+    let root = Node::new(CountedTtt(ttt::State::new()), None, None);
+    Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0));
+
+    let mut expanded = 0;
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if !node.borrow().children.is_empty() {
+            expanded += 1;
+        }
+        stack.extend(node.borrow().children.iter().map(Rc::clone));
+    }
+    assert!(expanded > 100);
+    assert_eq!(LEGAL_ACTION_CALLS.with(|calls| calls.get()), expanded);
+    assert_eq!(POLICY_CALLS.with(|calls| calls.get()), expanded);
+}
+
+#[test]
+fn several_rollouts_per_leaf_count_as_one_visit_with_their_mean() {
+    // Whoever takes the last counter wins, so each playout from two counters ends 1 or -1 for the first player
+    let state = Race { remaining: 2, player: 1 };
+    let single = SearchConfig::default();
+    let averaged = SearchConfig { rollouts_per_leaf: 4, ..SearchConfig::default() };
+    let mut means = HashSet::new();
+    for seed in 0..20 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let outcomes: Vec<f64> = (0..4).map(|_| rollout(&state, &single, &mut rng)[0]).collect();
+        let mean = rollout(&state, &averaged, &mut StdRng::seed_from_u64(seed))[0];
+        assert_eq!(mean, outcomes.iter().sum::<f64>() / 4.);
+        means.insert((mean * 4.) as i32);
+    }
+    assert!(means.len() > 2, "the means should include more than the two single outcomes");
+
+    let root = Node::new(state, None, None);
+    Node::simulate(&root, &averaged, None, &mut StdRng::seed_from_u64(0));
+    assert_eq!(root.borrow().visits(), 1);
+}
+
+// A token passed around a ring of four squares forever, so the same positions keep coming back and nobody wins
+#[derive(Clone)]
+struct Ring {
+    square: u8,
+    player: i32,
+}
+
+impl GameState for Ring {
+    type Action = u8;
+
+    fn status(&self) -> GameStatus {
+        GameStatus::InProgress
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.player
+    }
+
+    fn get_legal_actions(&self) -> Vec<u8> {
+        vec![1, 3]
+    }
+
+    fn get_policy(&self, actions: &[u8]) -> Vec<f64> {
+        vec![1. / actions.len() as f64; actions.len()]
+    }
+
+    fn get_next_state(&self, step: u8) -> Ring {
+        Ring { square: (self.square + step) % 4, player: -self.player }
+    }
+
+    fn hash_key(&self) -> u64 {
+        (self.square as u64) << 1 | (self.player == 1) as u64
+    }
+}
+
+#[test]
+fn playouts_of_a_cyclic_game_end_in_a_draw() {
+    let state = Ring { square: 0, player: 1 };
+    let config = SearchConfig { max_rollout_moves: 50, ..SearchConfig::default() };
+    assert_eq!(rollout(&state, &config, &mut StdRng::seed_from_u64(0)), vec![0., 0.]);
+
+    // The default cap ends them too, and a whole search with it
+    assert_eq!(rollout(&state, &SearchConfig::default(), &mut StdRng::seed_from_u64(0)), vec![0., 0.]);
+    let root = Node::new(state, None, None);
+    assert!(Node::best_action(&root, 50).is_some());
+    assert_eq!(root.borrow().total_values(), &[0., 0.]);
+}
+
+#[test]
+fn the_builder_rejects_each_invalid_field() {
+    let rejects = |builder: SearchConfigBuilder, field: &str| match builder.build() {
+        Err(ConfigError(message)) => assert!(message.starts_with(field), "{:?} doesn't name {}", message, field),
+        Ok(_) => panic!("a bad {} was accepted", field),
+    };
+    let builder = SearchConfig::builder;
+    rejects(builder().pb_c_init(-1.), "pb_c_init");
+    rejects(builder().pb_c_init(f64::INFINITY), "pb_c_init");
+    rejects(builder().pb_c_base(0.), "pb_c_base");
+    rejects(builder().pb_c_base(f64::NAN), "pb_c_base");
+    rejects(builder().n_simulations(0), "n_simulations");
+    rejects(builder().temperature(-0.5), "temperature");
+    rejects(builder().temperature(f64::NAN), "temperature");
+    rejects(builder().selection_policy(SelectionPolicy::Ucb1 { c: -1. }), "the UCB1 exploration constant");
+    rejects(builder().max_rollout_moves(0), "max_rollout_moves");
+    rejects(builder().rollout_confidence_cutoff(0.), "rollout_confidence_cutoff");
+    rejects(builder().rollout_confidence_cutoff(1.5), "rollout_confidence_cutoff");
+    rejects(builder().first_play_urgency(2.), "first_play_urgency");
+    rejects(builder().rollouts_per_leaf(0), "rollouts_per_leaf");
+    rejects(builder().max_nodes(0), "max_nodes");
+    rejects(builder().blunder_rate(1.5), "blunder_rate");
+    rejects(builder().komi(f64::NAN), "komi");
+    rejects(builder().progressive_bias(-1.), "progressive_bias");
+
+    let config = builder().n_simulations(5000).temperature(0.5).komi(0.1).build().unwrap();
+    assert_eq!((config.n_simulations, config.temperature, config.komi), (5000, 0.5, 0.1));
+    assert!(builder().build().is_ok());
+}
+
+#[test]
+fn max_value_and_max_visits_can_pick_different_moves() {
+    // The most visited move is only slightly ahead, a less visited one looks better, and a barely visited one best
+    let children: Vec<ChildStat<(usize, usize)>> = [((1, 1), 100, 0.1), ((0, 0), 40, 0.5), ((2, 2), 2, 0.9)].into_iter()
+        .map(|(action, visit_count, mean_value)| ChildStat { action, visit_count, mean_value, prior: 1. / 3. })
+        .collect();
+    let pick = |final_selection: FinalSelection| {
+        let config = SearchConfig { temperature: 0., final_selection, ..SearchConfig::default() };
+        final_action(&ttt::State::new(), &children, &config, &mut StdRng::seed_from_u64(0))
+    };
+    assert_eq!(pick(FinalSelection::MaxVisits), Some((1, 1)));
+    assert_eq!(pick(FinalSelection::MaxValue { min_visits: 10 }), Some((0, 0)));
+    assert_eq!(pick(FinalSelection::MaxValue { min_visits: 1 }), Some((2, 2)));
+    // Without a child past the threshold it falls back to the visits
+    assert_eq!(pick(FinalSelection::MaxValue { min_visits: 1000 }), Some((1, 1)));
+}
+
+#[test]
+fn pruning_leaves_only_the_block() {
+    let config = SearchConfig { prune_losing_moves: true, ..SearchConfig::default() };
+    fn children<State: GameState + Clone>(state: State, config: &SearchConfig) -> Vec<State::Action> where State::Action: Ord {
+        let root = Node::new(state, None, None);
+        Node::simulate(&root, config, None, &mut StdRng::seed_from_u64(0));
+        let mut actions: Vec<State::Action> = root.borrow().children.iter().filter_map(|child| child.borrow().parent_action).collect();
+        actions.sort();
+        actions
+    }
+
+    // O to move with X threatening column 0, so every other drop loses on X's reply
+    assert_eq!(children(connect4_after(&[0, 1, 0, 1, 0]), &config), vec![0]);
+    assert_eq!(children(ttt_after(&[(0, 0), (1, 1), (0, 1)]), &config), vec![(0, 2)]);
+    // X threatens (1, 2) and (2, 0) at once, so every move loses and none are pruned
+    let fork = ttt_after(&[(0, 0), (0, 1), (1, 1), (2, 2), (1, 0)]);
+    assert_eq!(children(fork.clone(), &config), fork.get_legal_actions());
+}
+
+// Says every position is won for the first player, with a uniform prior
+struct Optimist;
+
+impl ValuePolicyProvider<CountedTtt> for Optimist {
+    fn evaluate(&self, state: &CountedTtt) -> (f64, Vec<((usize, usize), f64)>) {
+        let actions = state.0.get_legal_actions();
+        let prior = 1. / actions.len() as f64;
+        (1., actions.into_iter().map(|action| (action, prior)).collect())
+    }
+}
+
+#[test]
+fn provider_values_are_backed_up_in_place_of_rollouts() {
+    // Too few simulations to reach a finished game, so every leaf is scored by the provider
+    let root = Node::new(CountedTtt(ttt::State::new()), None, None);
+    let config = SearchConfig { n_simulations: 20, ..SearchConfig::default() };
+    assert!(Node::best_action_with_provider(&root, &config, &Optimist).is_some());
+    assert_eq!(root.borrow().visits(), 20);
+    assert_eq!(root.borrow().total_values(), &[20., -20.]);
+    assert_eq!(POLICY_CALLS.with(|calls| calls.get()), 0);
+}
+
+#[test]
+fn value_for_parent_is_the_movers_mean() {
+    let root = Node::new(ttt::State::new(), None, None);
+    let greedy = SearchConfig { selection_policy: SelectionPolicy::Ucb1 { c: 0. }, ..SearchConfig::default() };
+    // (X's total, visits) of a child X moved into, the mean X sees, and what that scores in [0, 1]
+    let cases = [(4., 4, 1., 1.), (-4., 4, -1., 0.), (0., 4, 0., 0.5), (3., 4, 0.75, 0.875), (-1., 2, -0.5, 0.25)];
+    for (x_total, visit_count, mean, score) in cases {
+        let node = Node::new(root.borrow().state.get_next_state((1, 1)), Some(Rc::downgrade(&root)), Some((1, 1)));
+        node.borrow_mut().total_values = vec![x_total, -x_total];
+        node.borrow_mut().visit_count = visit_count;
+        assert_eq!(node.borrow().value_for_parent(), Some(mean));
+        // The same mean from O's side, O being the player to move at the child
+        assert_eq!(node.borrow().value(), -mean);
+        assert_eq!(selection_score(&greedy, 10, visit_count, Some(mean), 0.), score);
+    }
+
+    let unvisited = Node::new(root.borrow().state.get_next_state((0, 0)), Some(Rc::downgrade(&root)), Some((0, 0)));
+    assert_eq!(unvisited.borrow().value_for_parent(), None);
+    assert_eq!(root.borrow().value_for_parent(), None);
+}
+
+#[test]
+fn equal_scores_go_to_the_higher_prior_then_the_first_child() {
+    let config = SearchConfig { selection_policy: SelectionPolicy::Ucb1 { c: 1. }, ..SearchConfig::default() };
+    let tied_root = |priors: [f64; 3]| {
+        let root = Node::new(ttt::State::new(), None, None);
+        let actions = [(0, 0), (1, 1), (2, 2)];
+        for action in actions {
+            let node = Node::new(root.borrow().state.get_next_state(action), Some(Rc::downgrade(&root)), Some(action));
+            node.borrow_mut().total_values = vec![1., -1.];
+            node.borrow_mut().visit_count = 2;
+            root.borrow_mut().children.push(node);
+        }
+        root.borrow_mut().visit_count = 6;
+        root.borrow_mut().action_probs = Some(actions.into_iter().zip(priors).collect());
+        root
+    };
+    let pick = |priors| tied_root(priors).borrow().best_child(&config, None).borrow().parent_action;
+
+    assert_eq!(pick([0.2, 0.5, 0.3]), Some((1, 1)));
+    assert_eq!(pick([0.2, 0.3, 0.5]), Some((2, 2)));
+    assert_eq!(pick([0.4, 0.2, 0.4]), Some((0, 0)));
+    assert_eq!(pick([1. / 3.; 3]), Some((0, 0)));
+}
+
+// The first player picks one of two bets, then a roll settles the game: the first bet wins 70% of the time and
+// the second 30%
+#[derive(Clone)]
+enum Gamble {
+    Choose,
+    Roll { win_probability: f64 },
+    Settled { winner: i32 },
+}
+
+const BETS: [f64; 2] = [0.7, 0.3];
+
+impl GameState for Gamble {
+    type Action = usize;
+
+    fn status(&self) -> GameStatus {
+        match *self {
+            Gamble::Settled { winner } => GameStatus::Win(winner),
+            _ => GameStatus::InProgress,
+        }
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        1
+    }
+
+    fn get_legal_actions(&self) -> Vec<usize> {
+        match self {
+            Gamble::Choose => vec![0, 1],
+            _ => Vec::new(),
+        }
+    }
+
+    fn get_policy(&self, actions: &[usize]) -> Vec<f64> {
+        vec![1. / actions.len() as f64; actions.len()]
+    }
+
+    fn get_next_state(&self, bet: usize) -> Gamble {
+        Gamble::Roll { win_probability: BETS[bet] }
+    }
+
+    fn is_chance_node(&self) -> bool {
+        matches!(self, Gamble::Roll { .. })
+    }
+
+    fn get_chance_outcomes(&self) -> Vec<(Gamble, f64)> {
+        match *self {
+            Gamble::Roll { win_probability } => {
+                vec![(Gamble::Settled { winner: 1 }, win_probability), (Gamble::Settled { winner: -1 }, 1. - win_probability)]
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    fn hash_key(&self) -> u64 {
+        match *self {
+            Gamble::Choose => 0,
+            Gamble::Roll { win_probability } => 1 + (win_probability * 10.) as u64,
+            Gamble::Settled { winner } => 20 + (winner == 1) as u64,
+        }
+    }
+}
+
+#[test]
+fn the_search_samples_chance_outcomes_by_probability_and_takes_the_better_bet() {
+    let root = Node::new(Gamble::Choose, None, None);
+    let config = SearchConfig { n_simulations: 2000, ..SearchConfig::default() };
+    assert_eq!(Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0)), Some(0));
+
+    // Each roll's outcomes are visited about as often as they come up
+    for (bet, win_probability) in BETS.into_iter().enumerate() {
+        let roll = child(&root, bet).unwrap();
+        let roll = roll.borrow();
+        assert_eq!(roll.chance_weights, vec![win_probability, 1. - win_probability]);
+        let wins = roll.children[0].borrow().visit_count as f64;
+        let frequency = wins / roll.visit_count as f64;
+        assert!(roll.visit_count > 50, "bet {} only had {} visits", bet, roll.visit_count);
+        assert!((frequency - win_probability).abs() < 0.1, "bet {} won {:.2} of {} rolls", bet, frequency, roll.visit_count);
+    }
+
+    // Outcome edges have no action, so they're labelled with their probability
+    let dot = Node::to_dot(&root, 2);
+    assert!(dot.contains("[label=\"0\"]") && dot.contains("[label=\"p=0.70\"]") && dot.contains("[label=\"p=0.30\"]"));
+}
+
+// Nodes in the tree under `root`, counted one by one
+fn count_nodes<State: GameState + Clone>(root: &Rc<RefCell<Node<State>>>) -> usize {
+    let mut count = 0;
+    let mut stack = vec![Rc::clone(root)];
+    while let Some(node) = stack.pop() {
+        count += 1;
+        stack.extend(node.borrow().children.iter().map(Rc::clone));
+    }
+    count
+}
+
+#[test]
+fn the_tree_stays_within_its_node_budget_across_a_game() {
+    let config = SearchConfig { n_simulations: 500, max_nodes: Some(300), ..SearchConfig::default() };
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut root = Node::new(connect4::State::new(), None, None);
+    for _ in 0..12 {
+        let Some(action) = Node::best_action_with_rng(&root, &config, &mut rng) else { break };
+        let size = count_nodes(&root);
+        assert!(size <= 300, "the tree grew to {} nodes", size);
+        assert_eq!(Node::tree_size(&root), size);
+        // The subtree carried over keeps its own count
+        root = Node::advance(&root, action);
+        assert_eq!(Node::tree_size(&root), count_nodes(&root));
+    }
+}
+
+#[test]
+fn the_last_tier_of_an_iterative_search_matches_a_single_search() {
+    let config = SearchConfig { temperature: 0., ..SearchConfig::default() };
+    for seed in 0..3 {
+        let iterative = Node::new(connect4::State::new(), None, None);
+        let mut tiers = Vec::new();
+        let action = Node::best_action_iterative_with_rng(&iterative, &config, &[100, 200, 400, 800], &mut StdRng::seed_from_u64(seed), |completed, action| {
+            tiers.push((completed, action));
+            true
+        });
+        assert_eq!(tiers.iter().map(|&(completed, _)| completed).collect::<Vec<_>>(), vec![100, 200, 400, 800]);
+        assert_eq!(tiers.last().map(|&(_, action)| action), action);
+
+        let single = Node::new(connect4::State::new(), None, None);
+        let single_config = SearchConfig { n_simulations: 800, ..config.clone() };
+        assert_eq!(Node::best_action_with_rng(&single, &single_config, &mut StdRng::seed_from_u64(seed)), action);
+        let visits = |root: &Rc<RefCell<Node<connect4::State>>>| Node::child_stats(root).into_iter().map(|child| child.visit_count).collect::<Vec<_>>();
+        assert_eq!(visits(&iterative), visits(&single));
+    }
+
+    // Returning false from the callback stops the search after that tier
+    let root = Node::new(connect4::State::new(), None, None);
+    Node::best_action_iterative_with_rng(&root, &config, &[100, 200], &mut StdRng::seed_from_u64(0), |_, _| false);
+    assert_eq!(root.borrow().visits(), 100);
+}
+
+#[test]
+fn a_confident_evaluation_cuts_the_rollout_short() {
+    // X to move can win at once, which connect4's evaluate() scores as 0.9 without playing it
+    let state = connect4_after(&[0, 1, 0, 1, 0, 1]);
+    assert!(!state.is_terminal());
+    let cutoff = |cutoff| SearchConfig { rollout_confidence_cutoff: Some(cutoff), ..SearchConfig::default() };
+    for seed in 0..5 {
+        assert_eq!(rollout(&state, &cutoff(0.8), &mut StdRng::seed_from_u64(seed)), vec![0.9, -0.9]);
+        // A cutoff the evaluation doesn't reach leaves the playout to finish the game
+        let results = rollout(&state, &cutoff(0.95), &mut StdRng::seed_from_u64(seed));
+        assert!(results.iter().all(|result| [-1., 0., 1.].contains(result)), "{:?}", results);
+    }
+}
+
+#[test]
+fn always_blundering_draws_from_every_legal_move() {
+    let state = ttt_after(&[(1, 1), (0, 0)]);
+    let config = SearchConfig { n_simulations: 50, blunder_rate: 1., ..SearchConfig::default() };
+    let picks: HashSet<(usize, usize)> = (0..100)
+        .map(|seed| Node::best_action_with_rng(&Node::new(state.clone(), None, None), &config, &mut StdRng::seed_from_u64(seed)).unwrap())
+        .collect();
+    assert_eq!(picks, state.get_legal_actions().into_iter().collect());
+
+    // And never blundering plays the searched move every time
+    let config = SearchConfig { blunder_rate: 0., ..config };
+    let root = Node::new(state, None, None);
+    let action = Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0));
+    assert!((0..20).all(|seed| Node::select_final_action(&root, &config, &mut StdRng::seed_from_u64(seed)) == action));
+}
+
+// Result for the player to move with perfect play, by plain minimax: 1 for a win, 0 for a draw and -1 for a loss.
+// Only for games where the turn always passes to the opponent.
+fn perfect_result<State: GameState>(state: &State) -> i32 {
+    match state.status() {
+        GameStatus::Win(player) => if player == state.get_player_turn() { 1 } else { -1 },
+        GameStatus::Draw => 0,
+        GameStatus::InProgress => state.get_legal_actions().into_iter()
+            .map(|action| -perfect_result(&state.get_next_state(action)))
+            .max()
+            .unwrap_or(0),
+    }
+}
+
+#[test]
+fn endgame_action_finds_the_exact_win_near_the_end_of_connect4() {
+    let config = SearchConfig { n_simulations: 0, endgame_exact_depth: Some(8), ..SearchConfig::default() };
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut checked = 0;
+    while checked < 10 {
+        // Random games played on until eight cells are left
+        let mut state = connect4::State::new();
+        while !state.is_terminal() && state.move_count() < Some(34) {
+            let actions = state.get_legal_actions();
+            state = state.get_next_state(actions[rng.gen_range(0..actions.len())]);
+        }
+        // Only wins that take more than the next move, which decided_action() would find without looking ahead
+        if state.is_terminal() || perfect_result(&state) != 1 || decided_action(&state).is_some() {
+            continue;
+        }
+
+        let action = endgame_action(&state, 8).expect("The rest of the game is in reach");
+        assert_eq!(perfect_result(&state.get_next_state(action)), -1, "{:?} doesn't win", action);
+        let root = Node::new(state.clone(), None, None);
+        assert_eq!(Node::best_action_with_rng(&root, &config, &mut rng), Some(action));
+        checked += 1;
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn a_saved_tree_loads_back_the_same() {
+    // expand_one leaves moves pending on some nodes, which the save has to keep too
+    let config = SearchConfig { n_simulations: 500, expand_one: true, ..SearchConfig::default() };
+    let root = Node::new(connect4_after(&[3, 3]), None, None);
+    Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0));
+
+    let path = std::env::temp_dir().join(format!("mcts-{}-tree.json", std::process::id())).to_string_lossy().into_owned();
+    Node::save(&root, &path).unwrap();
+    let loaded = Node::<connect4::State>::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // Walk both trees side by side
+    assert_eq!(Node::tree_size(&loaded), Node::tree_size(&root));
+    let mut partly_expanded = 0;
+    let mut stack = vec![(Rc::clone(&root), Rc::clone(&loaded))];
+    while let Some((original, copy)) = stack.pop() {
+        let (original, copy) = (original.borrow(), copy.borrow());
+        assert_eq!(copy.state.hash_key(), original.state.hash_key());
+        assert_eq!(copy.parent_action, original.parent_action);
+        assert_eq!((copy.visit_count, &copy.total_values), (original.visit_count, &original.total_values));
+        assert_eq!(copy.action_probs, original.action_probs);
+        assert_eq!(copy.pending_actions, original.pending_actions);
+        partly_expanded += !original.pending_actions.is_empty() as usize;
+        assert_eq!(copy.subtree_size, original.subtree_size);
+        assert_eq!(copy.children.len(), original.children.len());
+        assert!(copy.children.iter().all(|child| child.borrow().parent.as_ref().and_then(Weak::upgrade).is_some()));
+        stack.extend(original.children.iter().cloned().zip(copy.children.iter().cloned()));
+    }
+
+    assert!(partly_expanded > 0);
+
+    // The loaded tree carries on searching from where the saved one stopped
+    Node::best_action_with_rng(&loaded, &config, &mut StdRng::seed_from_u64(1));
+    assert_eq!(loaded.borrow().visits(), 1000);
+}
+
+#[test]
+fn progressive_bias_fades_as_a_child_is_visited() {
+    let (prior, value) = (0.4, Some(0.2));
+    let plain = SearchConfig::default();
+    let biased = SearchConfig { progressive_bias: 2., ..SearchConfig::default() };
+    let mut last_bias = f64::INFINITY;
+    for visit_count in [0, 1, 10] {
+        let before = selection_score(&plain, 50, visit_count, value, prior);
+        let bias = selection_score(&biased, 50, visit_count, value, prior) - before;
+        assert!((bias - 2. * prior / (visit_count as f64 + 1.)).abs() < 1e-12, "bias {} at {} visits", bias, visit_count);
+        assert!(bias < last_bias);
+        last_bias = bias;
+
+        // Without a bias the score is exactly the plain one
+        let unbiased = SearchConfig { progressive_bias: 0., ..SearchConfig::default() };
+        assert_eq!(selection_score(&unbiased, 50, visit_count, value, prior), before);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn saved_trees_with_unusable_chance_weights_are_rejected() {
+    let root = Node::new(connect4::State::new(), None, None);
+    Node::best_action_with_rng(&root, &SearchConfig { n_simulations: 50, ..SearchConfig::default() }, &mut StdRng::seed_from_u64(0));
+    let path = std::env::temp_dir().join(format!("mcts-{}-bad-tree.json", std::process::id())).to_string_lossy().into_owned();
+    Node::save(&root, &path).unwrap();
+    let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+    // The root has a child for each of the seven columns
+    let load_with_root_weights = |weights: Vec<f64>| {
+        let mut saved = saved.clone();
+        saved[0]["chance_weights"] = weights.into();
+        std::fs::write(&path, saved.to_string()).unwrap();
+        Node::<connect4::State>::load(&path).map(|_| ()).map_err(|err| (err.kind(), err.to_string()))
+    };
+    let too_few = load_with_root_weights(vec![0.5, 0.5]);
+    assert!(matches!(&too_few, Err((std::io::ErrorKind::InvalidData, message)) if message.contains("2 chance weights for 7 children")), "{:?}", too_few);
+    let moves_as_outcomes = load_with_root_weights(vec![1. / 7.; 7]);
+    assert!(matches!(&moves_as_outcomes, Err((std::io::ErrorKind::InvalidData, message)) if message.contains("parent action")), "{:?}", moves_as_outcomes);
+    assert!(load_with_root_weights(Vec::new()).is_ok());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,169 @@
+use crate::mcts::{GameState, GameStatus};
+use crate::notation::parse_number;
+use crate::player::Player;
+#[cfg(feature = "tui")]
+use crate::mcts::Node;
+#[cfg(feature = "tui")]
+use crate::ui::{self, Renderer, InputHandler};
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "tui")]
+use crossterm::{
+    event::KeyCode,
+    style::{Color, ResetColor, SetForegroundColor},
+    Result,
+};
+
+
+pub const DEFAULT_PILES: [usize; 3] = [3, 4, 5];
+
+#[derive(Clone, Hash)]
+pub struct State {
+    piles: Vec<usize>,
+    player: Player,
+}
+
+impl State {
+    pub fn new(piles: Vec<usize>) -> State {
+        State {
+            piles,
+            player: Player::X,
+        }
+    }
+}
+
+impl GameState for State {
+    // (pile index, number of objects to take)
+    type Action = (usize, usize);
+
+    fn status(&self) -> GameStatus {
+        // Whoever took the last object wins, which is the player who just moved
+        if self.piles.iter().all(|&pile| pile == 0) {
+            GameStatus::Win(self.player.opponent().value())
+        } else {
+            GameStatus::InProgress
+        }
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.player.value()
+    }
+
+    fn get_legal_actions(&self) -> Vec<Self::Action> {
+        self.piles.iter().enumerate()
+            .flat_map(|(i, &pile)| (1..=pile).map(move |count| (i, count)))
+            .collect()
+    }
+
+    fn get_policy(&self, actions: &[Self::Action]) -> Vec<f64> {
+        vec![1.0 / actions.len() as f64; actions.len()]
+    }
+
+    fn get_next_state(&self, action: Self::Action) -> State {
+        let mut next_piles = self.piles.clone();
+        next_piles[action.0] -= action.1;
+        State {
+            piles: next_piles,
+            player: self.player.opponent(),
+        }
+    }
+
+    // The pile number from 1 and how many to take from it, e.g. "2-3" for three from the second pile
+    fn action_to_string(&self, &(pile, count): &Self::Action) -> String {
+        format!("{}-{}", pile + 1, count)
+    }
+
+    fn parse_action(&self, text: &str) -> Option<Self::Action> {
+        let (pile, count) = text.split_once('-')?;
+        let (pile, count) = (parse_number(pile)?, parse_number(count)?);
+        (pile >= 1 && pile <= self.piles.len() && count >= 1).then(|| (pile - 1, count))
+    }
+
+    fn hash_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+
+#[cfg(feature = "tui")]
+struct PileRenderer;
+
+#[cfg(feature = "tui")]
+impl Renderer<State> for PileRenderer {
+    fn render(&self, state: &State, (pile, count): (usize, usize), hint: Option<(usize, usize)>) {
+        for (i, &objects) in state.piles.iter().enumerate() {
+            let selected = if i == pile { count.min(objects) } else { 0 };
+            let marker = if i == pile { '>' } else { ' ' };
+            println!("{} {}: {}{}\r", marker, i + 1, "|".repeat(objects - selected), "x".repeat(selected));
+        }
+        println!("\r");
+        println!("Take {} from pile {}\r", count, pile + 1);
+        if let Some((pile, count)) = hint {
+            println!("{}Hint: take {} from pile {}{}\r", SetForegroundColor(Color::Yellow), count, pile + 1, ResetColor);
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+struct KeyHandler {
+    n_piles: usize,
+}
+
+#[cfg(feature = "tui")]
+impl InputHandler<State> for KeyHandler {
+    fn initial_cursor(&self) -> (usize, usize) {
+        (0, 1)
+    }
+
+    // Right can go past the pile's size, which fit_cursor() then takes back
+    fn move_cursor(&self, (pile, count): (usize, usize), key: KeyCode) -> Option<(usize, usize)> {
+        match key {
+            KeyCode::Up if pile > 0 => Some((pile - 1, 1)),
+            KeyCode::Down if pile < self.n_piles - 1 => Some((pile + 1, 1)),
+            KeyCode::Left if count > 1 => Some((pile, count - 1)),
+            KeyCode::Right => Some((pile, count + 1)),
+            _ => None,
+        }
+    }
+
+    // Keeps the count within the pile, which may have shrunk since the human's last move
+    fn fit_cursor(&self, state: &State, (pile, count): (usize, usize)) -> (usize, usize) {
+        (pile, count.min(state.piles[pile]).max(1))
+    }
+}
+
+
+#[cfg(feature = "tui")]
+pub fn play(piles: Vec<usize>) -> Result<()> {
+    let options = ui::Options { human: Some(Player::X.value()), ..ui::Options::default() };
+    let input_handler = KeyHandler { n_piles: piles.len() };
+    ui::run_game(State::new(piles), options, &PileRenderer, &input_handler, |root, simulations| Node::best_action(root, simulations.unwrap_or(1000)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::{self, SearchConfig};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn nim_sum(state: &State) -> usize {
+        state.piles.iter().fold(0, |sum, &pile| sum ^ pile)
+    }
+
+    #[test]
+    fn the_search_leaves_a_nim_sum_of_zero() {
+        // 3 ^ 4 ^ 5 is 2, so the player to move wins, but only by taking two from the first pile
+        let state = State::new(vec![3, 4, 5]);
+        assert_ne!(nim_sum(&state), 0);
+        // Random playouts hand each other the last object all the time, so leaving out the moves that let the
+        // opponent take it at once keeps them from drowning out the one good move
+        let config = SearchConfig { n_simulations: 50000, prune_losing_moves: true, quick_win: true, ..SearchConfig::default() };
+        let root = mcts::Node::new(state.clone(), None, None);
+        let action = mcts::Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0)).unwrap();
+        assert_eq!(nim_sum(&state.get_next_state(action)), 0, "took {:?}", action);
+    }
+}
@@ -0,0 +1,83 @@
+// Board coordinates shared by the games' move notation: a column letter from 'a' followed by a row number from 1,
+// e.g. "b2". Each game decides which end of the board row 1 is.
+
+pub(crate) fn square(col: usize, row: usize) -> String {
+    format!("{}{}", (b'a' + col as u8) as char, row + 1)
+}
+
+// The 0-based (col, row) named by square(), without any bounds check beyond the letter being a-z
+pub(crate) fn parse_square(text: &str) -> Option<(usize, usize)> {
+    let mut chars = text.chars();
+    let letter = chars.next().filter(char::is_ascii_lowercase)?;
+    let number = parse_number(chars.as_str()).filter(|&number| number > 0)?;
+    Some((letter as usize - 'a' as usize, number - 1))
+}
+
+// A number written with digits alone, which str::parse() would also take with a leading '+'
+pub(crate) fn parse_number(text: &str) -> Option<usize> {
+    if text.is_empty() || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::GameState;
+    use crate::{connect4, dots_and_boxes, gomoku, mancala, nim, othello, ttt};
+
+    // Every legal move of `state` and of the positions after its first few moves reads back as itself
+    fn round_trips<S: GameState>(mut state: S) {
+        for _ in 0..4 {
+            let actions = state.get_legal_actions();
+            for action in &actions {
+                let text = state.action_to_string(action);
+                assert_eq!(state.parse_action(&text).as_ref(), Some(action), "{:?} doesn't read back", text);
+            }
+            let Some(&action) = actions.last() else { break };
+            state = state.get_next_state(action);
+        }
+    }
+
+    #[test]
+    fn every_game_reads_its_own_notation_back() {
+        round_trips(ttt::State::new());
+        round_trips(connect4::State::new());
+        round_trips(connect4::State::with_dimensions(8, 9, 5));
+        round_trips(othello::State::new());
+        round_trips(gomoku::State::new());
+        round_trips(nim::State::new(vec![3, 4, 5]));
+        round_trips(dots_and_boxes::State::new(2, 3));
+        round_trips(mancala::State::new());
+    }
+
+    #[test]
+    fn moves_are_named_as_documented() {
+        assert_eq!(ttt::State::new().action_to_string(&(1, 1)), "b2");
+        assert_eq!(ttt::State::new().parse_action("a3"), Some((0, 0)));
+        assert_eq!(connect4::State::new().action_to_string(&3), "c4");
+        assert_eq!(nim::State::new(vec![3, 4, 5]).parse_action("2-3"), Some((1, 3)));
+        assert_eq!(othello::State::new().parse_action("pass"), Some(othello::Move::Pass));
+        assert_eq!(dots_and_boxes::State::new(3, 3).action_to_string(&(0, 1)), "ha1");
+        assert_eq!(mancala::State::new().parse_action("6"), Some(5));
+    }
+
+    #[test]
+    fn numbers_need_digits_alone() {
+        assert_eq!(parse_number("42"), Some(42));
+        for text in ["", "+4", "-4", " 4", "4 ", "4.0", "x"] {
+            assert_eq!(parse_number(text), None, "{:?} was read as a number", text);
+        }
+        assert_eq!(parse_square("b+2"), None);
+        assert_eq!(parse_square("b0"), None);
+
+        let connect4 = connect4::State::new();
+        for text in ["c+4", "c0", "c8", "c", "4", "C4"] {
+            assert_eq!(connect4.parse_action(text), None, "{:?} was read as a move", text);
+        }
+        assert_eq!(mancala::State::new().parse_action("+3"), None);
+        assert_eq!(nim::State::new(vec![3, 4, 5]).parse_action("+1-2"), None);
+    }
+}
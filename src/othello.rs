@@ -0,0 +1,239 @@
+use crate::mcts::{GameState, GameStatus};
+use crate::notation::{parse_square, square};
+use crate::player::Player;
+#[cfg(feature = "tui")]
+use crate::mcts::Node;
+#[cfg(feature = "tui")]
+use crate::ui::{self, Renderer, InputHandler};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "tui")]
+use crossterm::{
+    event::KeyCode,
+    style::{Color, ResetColor, SetForegroundColor},
+    Result,
+};
+
+
+const SIZE: usize = 8;
+const DIRECTIONS: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Move {
+    Place(usize, usize),
+    Pass,
+}
+
+#[derive(Clone, Hash)]
+pub struct State {
+    board: Vec<Vec<char>>,
+    player: Player,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
+}
+
+impl State {
+    pub fn new() -> State {
+        let mut board = vec![vec![' '; SIZE]; SIZE];
+        board[3][3] = 'O';
+        board[4][4] = 'O';
+        board[3][4] = 'X';
+        board[4][3] = 'X';
+        State {
+            board,
+            player: Player::X,
+        }
+    }
+
+    // Opponent disks that would be flipped if `player` placed a disk at (row, col)
+    fn get_flips(&self, row: usize, col: usize, player: Player) -> Vec<(usize, usize)> {
+        let mut flips = Vec::new();
+        if self.board[row][col] != ' ' {
+            return flips;
+        }
+
+        for &(dr, dc) in DIRECTIONS.iter() {
+            let mut line = Vec::new();
+            let (mut r, mut c) = (row as isize + dr, col as isize + dc);
+            while r >= 0 && r < SIZE as isize && c >= 0 && c < SIZE as isize {
+                let cell = self.board[r as usize][c as usize];
+                if cell == player.opponent().symbol() {
+                    line.push((r as usize, c as usize));
+                } else {
+                    if cell == player.symbol() {
+                        flips.extend(line.iter());
+                    }
+                    break;
+                }
+                r += dr;
+                c += dc;
+            }
+        }
+
+        flips
+    }
+
+    fn get_placements(&self, player: Player) -> Vec<(usize, usize)> {
+        (0..SIZE)
+            .flat_map(|row| (0..SIZE).map(move |col| (row, col)))
+            .filter(|&(row, col)| !self.get_flips(row, col, player).is_empty())
+            .collect()
+    }
+
+    fn has_placement(&self, player: Player) -> bool {
+        (0..SIZE).any(|row| (0..SIZE).any(|col| !self.get_flips(row, col, player).is_empty()))
+    }
+
+    fn count(&self, player: Player) -> usize {
+        self.board.iter().map(|row| row.iter().filter(|&&cell| cell == player.symbol()).count()).sum()
+    }
+}
+
+impl GameState for State {
+    type Action = Move;
+
+    fn status(&self) -> GameStatus {
+        if self.has_placement(self.player) || self.has_placement(self.player.opponent()) {
+            return GameStatus::InProgress;
+        }
+        match self.count(Player::X).cmp(&self.count(Player::O)) {
+            Ordering::Greater => GameStatus::Win(Player::X.value()),
+            Ordering::Less => GameStatus::Win(Player::O.value()),
+            Ordering::Equal => GameStatus::Draw,
+        }
+    }
+
+    fn get_player_turn(&self) -> i32 {
+        self.player.value()
+    }
+
+    fn get_legal_actions(&self) -> Vec<Self::Action> {
+        let placements = self.get_placements(self.player);
+        if !placements.is_empty() {
+            placements.into_iter().map(|(row, col)| Move::Place(row, col)).collect()
+        } else if self.has_placement(self.player.opponent()) {
+            vec![Move::Pass]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn get_policy(&self, actions: &[Self::Action]) -> Vec<f64> {
+        vec![1.0 / actions.len() as f64; actions.len()]
+    }
+
+    fn get_next_state(&self, action: Self::Action) -> State {
+        let mut next_board = self.board.clone();
+        if let Move::Place(row, col) = action {
+            for (r, c) in self.get_flips(row, col, self.player) {
+                next_board[r][c] = self.player.symbol();
+            }
+            next_board[row][col] = self.player.symbol();
+        }
+
+        State {
+            board: next_board,
+            player: self.player.opponent(),
+        }
+    }
+
+    // Scored by the disk margin rather than just the winner
+    fn get_result(&self) -> f64 {
+        if !self.is_terminal() {
+            return 0.;
+        }
+        (self.count(Player::X) as f64 - self.count(Player::O) as f64) / (SIZE * SIZE) as f64
+    }
+
+    // The usual othello notation, with rows numbered from 1 at the top, e.g. "d3", and "pass"
+    fn action_to_string(&self, action: &Self::Action) -> String {
+        match *action {
+            Move::Place(row, col) => square(col, row),
+            Move::Pass => "pass".to_string(),
+        }
+    }
+
+    fn parse_action(&self, text: &str) -> Option<Self::Action> {
+        if text == "pass" {
+            return Some(Move::Pass);
+        }
+        parse_square(text).filter(|&(col, row)| col < SIZE && row < SIZE).map(|(col, row)| Move::Place(row, col))
+    }
+
+    fn hash_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+
+#[cfg(feature = "tui")]
+struct BoardRenderer;
+
+#[cfg(feature = "tui")]
+impl Renderer<State> for BoardRenderer {
+    fn render(&self, state: &State, cursor: Move, hint: Option<Move>) {
+        println!("+---+---+---+---+---+---+---+---+\r");
+        for i in 0..SIZE {
+            for j in 0..SIZE {
+                let mut cell = state.board[i][j].to_string();
+                if hint == Some(Move::Place(i, j)) {
+                    cell = format!("{}*{}", SetForegroundColor(Color::Yellow), ResetColor);
+                }
+                let marker = if cursor == Move::Place(i, j) { format!("[{}]", cell) } else { format!(" {} ", cell) };
+                print!("|{}", marker);
+            }
+            println!("|\r");
+            println!("+---+---+---+---+---+---+---+---+\r")
+        }
+        println!("X: {}  O: {}\r", state.count(Player::X), state.count(Player::O));
+        if cursor == Move::Pass && state.get_legal_actions() == vec![Move::Pass] {
+            println!("No moves, press Enter to pass\r");
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+struct KeyHandler;
+
+#[cfg(feature = "tui")]
+impl InputHandler<State> for KeyHandler {
+    fn initial_cursor(&self) -> Move {
+        Move::Place(2, 3)
+    }
+
+    fn move_cursor(&self, cursor: Move, key: KeyCode) -> Option<Move> {
+        let Move::Place(row, col) = cursor else { return None };
+        match key {
+            KeyCode::Up if row > 0 => Some(Move::Place(row - 1, col)),
+            KeyCode::Down if row < SIZE - 1 => Some(Move::Place(row + 1, col)),
+            KeyCode::Left if col > 0 => Some(Move::Place(row, col - 1)),
+            KeyCode::Right if col < SIZE - 1 => Some(Move::Place(row, col + 1)),
+            _ => None,
+        }
+    }
+
+    // A pass is only ever forced, so the cursor sits on it until the human has somewhere to play again
+    fn fit_cursor(&self, state: &State, cursor: Move) -> Move {
+        if state.get_legal_actions() == vec![Move::Pass] {
+            Move::Pass
+        } else if cursor == Move::Pass {
+            self.initial_cursor()
+        } else {
+            cursor
+        }
+    }
+}
+
+
+#[cfg(feature = "tui")]
+pub fn play() -> Result<()> {
+    let options = ui::Options { human: Some(Player::X.value()), ..ui::Options::default() };
+    ui::run_game(State::new(), options, &BoardRenderer, &KeyHandler, |root, simulations| Node::best_action(root, simulations.unwrap_or(1000)))
+}
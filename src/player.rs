@@ -0,0 +1,86 @@
+use std::fmt;
+
+// A side in the two-player games, X moving first. GameState::get_player_turn() is value(), so 1 for X and
+// -1 for O.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Player {
+    X,
+    O,
+}
+
+impl Player {
+    pub fn value(self) -> i32 {
+        match self {
+            Player::X => 1,
+            Player::O => -1,
+        }
+    }
+
+    // The player whose value() is `value`, which has to be 1 or -1
+    pub fn from_value(value: i32) -> Option<Player> {
+        match value {
+            1 => Some(Player::X),
+            -1 => Some(Player::O),
+            _ => None,
+        }
+    }
+
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::X => Player::O,
+            Player::O => Player::X,
+        }
+    }
+
+    // 0 for X and 1 for O, e.g. to index a per-player array
+    pub fn index(self) -> usize {
+        match self {
+            Player::X => 0,
+            Player::O => 1,
+        }
+    }
+
+    // The default mark, 'X' or 'O'
+    pub fn symbol(self) -> char {
+        match self {
+            Player::X => 'X',
+            Player::O => 'O',
+        }
+    }
+
+    pub fn from_symbol(symbol: char) -> Option<Player> {
+        match symbol {
+            'X' => Some(Player::X),
+            'O' => Some(Player::O),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Player {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+// A player as a save stores them: by name, or in saves from before Player, by the mark they were drawn with.
+// Only the game's symbols can tell which player a mark was, so resolve() takes them.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+pub(crate) enum StoredPlayer {
+    Player(Player),
+    Mark(char),
+}
+
+#[cfg(feature = "serde")]
+impl StoredPlayer {
+    // `symbols` is the first player's mark, then the second's
+    pub(crate) fn resolve(self, symbols: [char; 2]) -> Player {
+        match self {
+            StoredPlayer::Player(player) => player,
+            StoredPlayer::Mark(mark) => if mark == symbols[0] { Player::X } else { Player::O },
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Serialize, de::DeserializeOwned};
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Result, Write};
+
+pub fn save_game<S: Serialize>(state: &S, path: &str) -> Result<()> {
+    let json = serde_json::to_string_pretty(state).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+    fs::write(path, json)
+}
+
+pub fn load_game<S: DeserializeOwned>(path: &str) -> Result<S> {
+    let json = fs::read_to_string(path)?;
+    serde_json::from_str(&json).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+}
+
+// Appends `record` to a JSON lines file as a single line, creating the file if needed
+pub fn append_record<R: Serialize>(record: &R, path: &str) -> Result<()> {
+    let json = serde_json::to_string(record).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", json)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::{GameState, GameStatus, SearchConfig};
+    use crate::tournament::{record_game, GameRecord};
+    use crate::{connect4, ttt};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // A path in the temp directory that no other test uses
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("mcts-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    // Saves `state`, loads it back and checks that it's the same position
+    fn round_trip<S: GameState + Serialize + DeserializeOwned>(state: &S, name: &str) -> S {
+        let path = temp_path(name);
+        save_game(state, &path).unwrap();
+        let loaded: S = load_game(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), serde_json::to_string(state).unwrap());
+        assert_eq!(loaded.hash_key(), state.hash_key());
+        assert_eq!(loaded.get_player_turn(), state.get_player_turn());
+        loaded
+    }
+
+    #[test]
+    fn games_round_trip_through_a_save_file() {
+        let state = [(1, 1), (0, 0), (2, 0)].into_iter().fold(ttt::State::new().with_history(), |state, action| state.get_next_state(action));
+        let loaded = round_trip(&state, "ttt.json");
+        assert_eq!(loaded.history(), Some(&[(1, 1), (0, 0), (2, 0)][..]));
+
+        let state = [3, 3, 2, 4].into_iter().fold(connect4::State::new(), |state, action| state.get_next_state(action));
+        let loaded = round_trip(&state, "connect4.json");
+        assert_eq!(loaded.get_legal_actions(), state.get_legal_actions());
+        assert_eq!(loaded.get_next_state(1).status(), state.get_next_state(1).status());
+    }
+
+    #[test]
+    fn a_missing_save_is_an_error() {
+        assert!(load_game::<ttt::State>(&temp_path("missing.json")).is_err());
+    }
+
+    #[test]
+    fn records_append_one_per_line() {
+        let config = SearchConfig { n_simulations: 100, ..SearchConfig::default() };
+        let records: Vec<GameRecord<(usize, usize)>> = (0..2)
+            .map(|seed| record_game(ttt::State::new(), [&config, &config], &mut StdRng::seed_from_u64(seed)))
+            .collect();
+        let path = temp_path("records.jsonl");
+        for record in &records {
+            append_record(record, &path).unwrap();
+        }
+        let lines = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let loaded: Vec<GameRecord<(usize, usize)>> = lines.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        // serde_json can be an ulp out when it parses a float back, so the shares are compared loosely
+        assert_eq!(loaded.len(), records.len());
+        for (loaded, record) in loaded.iter().zip(&records) {
+            assert_eq!(loaded.result, record.result);
+            assert_eq!(loaded.moves.len(), record.moves.len());
+            for (loaded, entry) in loaded.moves.iter().zip(&record.moves) {
+                assert_eq!((loaded.state_hash, loaded.action), (entry.state_hash, entry.action));
+                assert_eq!(loaded.visit_distribution.len(), entry.visit_distribution.len());
+                for (&(loaded_action, loaded_share), &(action, share)) in loaded.visit_distribution.iter().zip(&entry.visit_distribution) {
+                    assert_eq!(loaded_action, action);
+                    assert!((loaded_share - share).abs() < 1e-12);
+                }
+            }
+        }
+    }
+
+    // The JSON of `state` as saves had it before Player, with the side to move and any winner stored as their mark
+    fn with_marks(state: &impl Serialize, player: char, winner: Option<char>) -> String {
+        let mut json = serde_json::to_value(state).unwrap();
+        json["player"] = player.to_string().into();
+        if let Some(winner) = winner {
+            json["winner"] = winner.to_string().into();
+        }
+        json.to_string()
+    }
+
+    #[test]
+    fn saves_that_stored_custom_marks_still_load() {
+        let state = ttt::State::new().with_symbols(['●', '○']).get_next_state((1, 1));
+        let loaded: ttt::State = serde_json::from_str(&with_marks(&state, '○', None)).unwrap();
+        assert_eq!(loaded.get_player_turn(), -1);
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), serde_json::to_string(&state).unwrap());
+
+        // X wins along the bottom row
+        let state = [0, 0, 1, 1, 2, 2, 3].into_iter().fold(connect4::State::new().with_symbols(['●', '○']), |state, action| state.get_next_state(action));
+        let loaded: connect4::State = serde_json::from_str(&with_marks(&state, '○', Some('●'))).unwrap();
+        assert_eq!(loaded.get_player_turn(), -1);
+        assert_eq!(loaded.status(), GameStatus::Win(1));
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), serde_json::to_string(&state).unwrap());
+
+        // Saves that name the player load as they always did, custom marks or not
+        let loaded: connect4::State = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert_eq!(loaded.status(), GameStatus::Win(1));
+    }
+}
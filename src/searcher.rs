@@ -0,0 +1,92 @@
+use crate::mcts::{Node, GameState, SearchConfig, default_rng, shortcut_action};
+use rand::prelude::*;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+
+// A search that runs a few simulations at a time, so an event loop can keep rendering and handling input
+// in between. config.n_simulations is ignored; the caller decides how many to run with step().
+pub struct Searcher<State: GameState, R: Rng = StdRng> {
+    root: Rc<RefCell<Node<State>>>,
+    config: SearchConfig,
+    rng: R,
+    simulations: usize,
+}
+
+impl<State: GameState + Clone> Searcher<State> {
+    pub fn new(state: State, config: SearchConfig) -> Self {
+        let rng = StdRng::from_rng(default_rng()).expect("seeding from another generator can't fail");
+        Searcher::with_rng(state, config, rng)
+    }
+}
+
+impl<State: GameState + Clone, R: Rng> Searcher<State, R> {
+    pub fn with_rng(state: State, config: SearchConfig, rng: R) -> Self {
+        Searcher {
+            root: Node::new(state, None, None),
+            config,
+            rng,
+            simulations: 0,
+        }
+    }
+
+    // Runs `n` more simulations, doing nothing once the root is terminal
+    pub fn step(&mut self, n: usize) {
+        if self.root.borrow().state.is_terminal() {
+            return;
+        }
+        for _ in 0..n {
+            Node::simulate(&self.root, &self.config, None, &mut self.rng);
+        }
+        self.simulations += n;
+    }
+
+    // The move the simulations so far point to, chosen as a full search would, or None if the game is over
+    pub fn best_action(&mut self) -> Option<State::Action> {
+        if self.root.borrow().state.is_terminal() {
+            return None;
+        }
+        if let Some(action) = shortcut_action(&self.root.borrow().state, &self.config) {
+            return Some(action);
+        }
+        Node::select_final_action(&self.root, &self.config, &mut self.rng)
+    }
+
+    // Total simulations run by step()
+    pub fn simulations(&self) -> usize {
+        self.simulations
+    }
+
+    pub fn root(&self) -> &Rc<RefCell<Node<State>>> {
+        &self.root
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect4;
+
+    // Each move searched and how many visits it got
+    fn visits<State: GameState + Clone>(root: &Rc<RefCell<Node<State>>>) -> Vec<(State::Action, i32)> {
+        Node::child_stats(root).into_iter().map(|child| (child.action, child.visit_count)).collect()
+    }
+
+    #[test]
+    fn steps_add_up_to_a_single_search() {
+        for seed in 0..3 {
+            let mut searcher = Searcher::with_rng(connect4::State::new(), SearchConfig::default(), StdRng::seed_from_u64(seed));
+            for _ in 0..10 {
+                searcher.step(100);
+            }
+            assert_eq!(searcher.simulations(), 1000);
+
+            let root = Node::new(connect4::State::new(), None, None);
+            let config = SearchConfig { n_simulations: 1000, ..SearchConfig::default() };
+            let action = Node::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(seed));
+            assert_eq!(visits(searcher.root()), visits(&root));
+            assert_eq!(searcher.best_action(), action);
+        }
+    }
+}
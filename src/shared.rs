@@ -0,0 +1,303 @@
+use crate::mcts::{ChildStat, ConfigError, GameState, SearchConfig, check_supported, compare_scores, default_rng, final_action, mean_value, prune_losing_moves, rollout, selection_score, shortcut_action};
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+
+// Thread-safe counterpart to Node, for a tree that has to be shared between threads, e.g. one engine behind a
+// server. Each node sits in its own Arc<Mutex<..>>, and a search holds at most a parent and one of its children
+// locked at a time, so several threads can read or search the same tree at once.
+//
+// On one thread the locks are uncontended and cheap, and a search runs at about the same speed as with Node. The
+// cost shows up when threads search together: every simulation goes through the root's lock, so they queue at the
+// top of the tree, and with no virtual loss they also tend to follow the same path. best_action_parallel(), with
+// a tree per thread, makes better use of several cores. Holding a node's lock while calling one of the functions
+// here on the same node deadlocks, as with any Mutex. Chance nodes aren't supported here, and configs with
+// max_nodes or expand_one are rejected. The final move follows the rest of the config as it does for Node.
+
+pub struct SharedNode<State: GameState> {
+    pub state: State,
+    parent: Option<Weak<Mutex<Self>>>,
+    parent_action: Option<State::Action>,
+    children: Vec<Arc<Mutex<Self>>>,
+    total_values: Vec<f64>,
+    visit_count: i32,
+    action_probs: HashMap<State::Action, f64>,
+}
+
+// Unlinks uniquely owned children into a worklist, as Node's Drop does, so deep trees don't overflow the stack
+impl<State: GameState> Drop for SharedNode<State> {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(child) = stack.pop() {
+            if let Ok(child) = Arc::try_unwrap(child) {
+                if let Ok(mut child) = child.into_inner() {
+                    stack.append(&mut child.children);
+                }
+            }
+        }
+    }
+}
+
+impl<State: GameState + Clone> SharedNode<State> {
+    pub fn new(state: State) -> Arc<Mutex<Self>> {
+        SharedNode::with_parent(state, None, None)
+    }
+
+    fn with_parent(state: State, parent: Option<Weak<Mutex<Self>>>, parent_action: Option<State::Action>) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(SharedNode {
+            total_values: vec![0.; state.num_players()],
+            state,
+            parent,
+            parent_action,
+            children: Vec::new(),
+            visit_count: 0,
+            action_probs: HashMap::new(),
+        }))
+    }
+
+    pub fn visits(&self) -> i32 {
+        self.visit_count
+    }
+
+    // Mean value in [-1, 1] for the player to move at this node, or -1 if it hasn't been visited, as in Node::value()
+    pub fn value(&self) -> f64 {
+        mean_value(&self.total_values, self.state.get_player_index(), self.visit_count).unwrap_or(-1.)
+    }
+
+    // Step 1: Select and expand
+
+    fn select_node(root: &Arc<Mutex<Self>>, config: &SearchConfig) -> Arc<Mutex<Self>> {
+        let mut current_node = Arc::clone(root);
+
+        loop {
+            // Checking for children and expanding happen under the same lock, so only one thread expands a node
+            let step = {
+                let mut node = current_node.lock().unwrap();
+                if node.state.is_terminal() {
+                    None
+                } else {
+                    let expanded = node.children.is_empty();
+                    if expanded {
+                        node.expand(Arc::downgrade(&current_node), config);
+                    }
+                    // No legal moves, so treat it as a terminal leaf
+                    if node.children.is_empty() { None } else { Some((node.best_child(config), expanded)) }
+                }
+            };
+            match step {
+                Some((child, false)) => current_node = child,
+                Some((child, true)) => return child,
+                None => return current_node,
+            }
+        }
+    }
+
+    fn best_child(&self, config: &SearchConfig) -> Arc<Mutex<Self>> {
+        let player = self.state.get_player_index();
+        let scores: Vec<(f64, f64)> = self.children.iter().map(|child| {
+            let child = child.lock().unwrap();
+            let prior = self.action_probs.get(&child.parent_action.unwrap()).copied().unwrap_or(0.0);
+            let value = mean_value(&child.total_values, player, child.visit_count);
+            (selection_score(config, self.visit_count, child.visit_count, value, prior), prior)
+        }).collect();
+        // Ties go to the higher prior, then to the child expanded first, as in Node::best_child()
+        let best = (0..self.children.len()).rev().max_by(|&a, &b| {
+            compare_scores(scores[a].0, scores[b].0).then_with(|| compare_scores(scores[a].1, scores[b].1))
+        }).expect("Unable to find best child node");
+        Arc::clone(&self.children[best])
+    }
+
+    fn expand(&mut self, this: Weak<Mutex<Self>>, config: &SearchConfig) {
+        let state = self.state.clone();
+        let actions = state.get_legal_actions();
+        let weights = state.get_policy(&actions);
+        self.action_probs = actions.iter().copied().zip(weights).collect();
+        let mut children: Vec<(State::Action, State)> = actions.iter()
+            .filter(|action| self.action_probs.get(action).is_some_and(|&prob| prob > 1e-6 || prob.is_nan()))
+            .map(|&action| (action, state.get_next_state(action)))
+            .collect();
+        if config.prune_losing_moves {
+            prune_losing_moves(state.get_player_turn(), &mut children);
+        }
+        for (action, child_state) in children {
+            self.children.push(SharedNode::with_parent(child_state, Some(this.clone()), Some(action)));
+        }
+    }
+
+    // Step 2: Backpropagate the results of a rollout, locking one node at a time on the way up
+
+    fn backpropagate(node: &Arc<Mutex<Self>>, results: &[f64]) {
+        let mut current_node_option = Some(Arc::clone(node));
+        while let Some(current_node_arc) = current_node_option {
+            let mut current_node = current_node_arc.lock().unwrap();
+            current_node.visit_count += 1;
+            current_node.total_values.iter_mut().zip(results).for_each(|(total, result)| *total += result);
+            current_node_option = current_node.parent.as_ref().and_then(Weak::upgrade);
+        }
+    }
+
+    // Tree search
+
+    pub fn best_action(root: &Arc<Mutex<Self>>, n_simulations: i32) -> Option<State::Action> {
+        let config = SearchConfig { n_simulations, ..SearchConfig::default() };
+        SharedNode::best_action_with_rng(root, &config, &mut default_rng()).expect("the default config is supported")
+    }
+
+    // Safe to call from several threads on the same root at once, in which case their simulations add up
+    pub fn best_action_with_rng<R: Rng>(root: &Arc<Mutex<Self>>, config: &SearchConfig, rng: &mut R) -> Result<Option<State::Action>, ConfigError> {
+        check_supported(config, "the shared tree")?;
+        let state = root.lock().unwrap().state.clone();
+        if state.is_terminal() {
+            return Ok(None);
+        }
+        if let Some(action) = shortcut_action(&state, config) {
+            return Ok(Some(action));
+        }
+        for _ in 0..config.n_simulations {
+            let leaf = SharedNode::select_node(root, config);
+            let leaf_state = leaf.lock().unwrap().state.clone();
+            let results = rollout(&leaf_state, config, rng);
+            SharedNode::backpropagate(&leaf, &results);
+        }
+        Ok(final_action(&state, &SharedNode::child_stats(root), config, rng))
+    }
+
+    // Stats of the root's children, as in Node::child_stats()
+    fn child_stats(root: &Arc<Mutex<Self>>) -> Vec<ChildStat<State::Action>> {
+        let root = root.lock().unwrap();
+        let player = root.state.get_player_index();
+        root.children.iter()
+            .filter_map(|child| {
+                let child = child.lock().unwrap();
+                child.parent_action.map(|action| ChildStat {
+                    action,
+                    visit_count: child.visit_count,
+                    mean_value: mean_value(&child.total_values, player, child.visit_count).unwrap_or(0.),
+                    prior: root.action_probs.get(&action).copied().unwrap_or(0.),
+                })
+            })
+            .collect()
+    }
+
+    // Share of the root visits each move has had, as in Node::action_distribution()
+    pub fn action_distribution(root: &Arc<Mutex<Self>>) -> Vec<(State::Action, f64)> {
+        let root = root.lock().unwrap();
+        let visits: Vec<(Option<State::Action>, i32)> = root.children.iter().map(|child| {
+            let child = child.lock().unwrap();
+            (child.parent_action, child.visit_count)
+        }).collect();
+        let total_visits: i32 = visits.iter().map(|&(_, visit_count)| visit_count).sum();
+        if total_visits == 0 {
+            return Vec::new();
+        }
+        visits.into_iter()
+            .filter_map(|(action, visit_count)| action.map(|action| (action, visit_count as f64 / total_visits as f64)))
+            .collect()
+    }
+
+    // Number of nodes in the tree, including the root
+    pub fn tree_size(root: &Arc<Mutex<Self>>) -> usize {
+        let mut size = 0;
+        let mut stack = vec![Arc::clone(root)];
+        while let Some(node) = stack.pop() {
+            size += 1;
+            stack.extend(node.lock().unwrap().children.iter().map(Arc::clone));
+        }
+        size
+    }
+
+    // Keeps the subtree under `action` as the new root, or starts afresh if it was never expanded
+    pub fn advance(root: &Arc<Mutex<Self>>, action: State::Action) -> Arc<Mutex<Self>> {
+        let mut root = root.lock().unwrap();
+        let index = root.children.iter().position(|child| child.lock().unwrap().parent_action == Some(action));
+        match index {
+            Some(index) => {
+                let child = root.children.swap_remove(index);
+                child.lock().unwrap().parent = None;
+                child
+            },
+            None => SharedNode::with_parent(root.state.get_next_state(action), None, Some(action)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::FinalSelection;
+    use crate::{connect4, ttt};
+
+    #[test]
+    fn configs_the_shared_tree_cant_follow_are_rejected() {
+        let root = SharedNode::new(ttt::State::new());
+        let mut rng = StdRng::seed_from_u64(0);
+        let max_nodes = SearchConfig { max_nodes: Some(100), ..SearchConfig::default() };
+        assert!(SharedNode::best_action_with_rng(&root, &max_nodes, &mut rng).is_err());
+        let expand_one = SearchConfig { expand_one: true, ..SearchConfig::default() };
+        assert!(SharedNode::best_action_with_rng(&root, &expand_one, &mut rng).is_err());
+        assert_eq!(root.lock().unwrap().visits(), 0);
+    }
+
+    #[test]
+    fn the_final_move_follows_the_config() {
+        let root = SharedNode::new(ttt::State::new());
+        let config = SearchConfig { n_simulations: 500, ..SearchConfig::default() };
+        let action = SharedNode::best_action_with_rng(&root, &config, &mut StdRng::seed_from_u64(0)).unwrap();
+        let children = SharedNode::child_stats(&root);
+        let most_visited = children.iter().max_by_key(|child| child.visit_count).map(|child| child.action);
+        assert_eq!(action, most_visited);
+
+        // A search of one more simulation picks by the config's final selection and blunder rate too
+        let max_value = SearchConfig { n_simulations: 1, final_selection: FinalSelection::MaxValue { min_visits: 1 }, ..SearchConfig::default() };
+        let action = SharedNode::best_action_with_rng(&root, &max_value, &mut StdRng::seed_from_u64(0)).unwrap();
+        let children = SharedNode::child_stats(&root);
+        let best_valued = children.iter().max_by(|a, b| compare_scores(a.mean_value, b.mean_value)).map(|child| child.action);
+        assert_eq!(action, best_valued);
+
+        let always_blunder = SearchConfig { n_simulations: 1, blunder_rate: 1., ..SearchConfig::default() };
+        let picks: std::collections::HashSet<_> = (0..30)
+            .map(|seed| SharedNode::best_action_with_rng(&root, &always_blunder, &mut StdRng::seed_from_u64(seed)).unwrap().unwrap())
+            .collect();
+        assert!(picks.len() > 3);
+    }
+
+    #[test]
+    fn searches_from_two_threads_add_up() {
+        let root = SharedNode::new(connect4::State::new());
+        std::thread::scope(|scope| {
+            for (seed, n_simulations) in [(0, 300), (1, 500)] {
+                let root = &root;
+                scope.spawn(move || {
+                    let config = SearchConfig { n_simulations, ..SearchConfig::default() };
+                    SharedNode::best_action_with_rng(root, &config, &mut StdRng::seed_from_u64(seed)).unwrap();
+                });
+            }
+        });
+        assert_eq!(root.lock().unwrap().visits(), 800);
+
+        // Every node's children account for no more visits than it had, and point back at it as their parent
+        let mut stack = vec![Arc::clone(&root)];
+        let mut size = 0;
+        while let Some(node_arc) = stack.pop() {
+            size += 1;
+            let node = node_arc.lock().unwrap();
+            let child_visits: i32 = node.children.iter().map(|child| child.lock().unwrap().visits()).sum();
+            assert!(child_visits <= node.visits());
+            for child in &node.children {
+                let parent = child.lock().unwrap().parent.as_ref().and_then(Weak::upgrade).unwrap();
+                assert!(Arc::ptr_eq(&parent, &node_arc));
+            }
+            stack.extend(node.children.iter().map(Arc::clone));
+        }
+        assert_eq!(SharedNode::tree_size(&root), size);
+        assert!(size > 8);
+
+        // Each simulation went through one of the root's moves
+        let distribution = SharedNode::action_distribution(&root);
+        assert_eq!(distribution.len(), 7);
+        assert!((distribution.iter().map(|(_, share)| share).sum::<f64>() - 1.).abs() < 1e-9);
+        let root = root.lock().unwrap();
+        assert_eq!(root.children.iter().map(|child| child.lock().unwrap().visits()).sum::<i32>(), 800);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::mcts::{Node, GameState, SearchConfig, default_rng};
+use rand::prelude::*;
+
+
+// Tally of a match from the perspective of the first config
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchResult {
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+// One move of a recorded game
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveRecord<Action> {
+    pub state_hash: u64,  // hash_key() of the position the move was played from
+    pub action: Action,
+    // Share of the root visits each move got, summing to 1. A move chosen without searching, e.g. by quick_win,
+    // gets all of it.
+    pub visit_distribution: Vec<(Action, f64)>,
+}
+
+// A whole game move by move, e.g. as training data. With the serde feature, save::append_record() writes these
+// out one per line.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameRecord<Action> {
+    pub moves: Vec<MoveRecord<Action>>,
+    pub result: f64,  // The final get_result()
+}
+
+// Plays a single game without any UI, with configs[i] searching for the player whose get_player_index() is i.
+// Each agent keeps its own tree and advances it past both players' moves. Returns the final get_result().
+pub fn play_game<State: GameState + Clone, R: Rng>(initial_state: State, configs: [&SearchConfig; 2], rng: &mut R) -> f64 {
+    record_game(initial_state, configs, rng).result
+}
+
+// Like play_game(), but keeps each move along with how the search spread its visits
+pub fn record_game<State: GameState + Clone, R: Rng>(initial_state: State, configs: [&SearchConfig; 2], rng: &mut R) -> GameRecord<State::Action> {
+    let mut roots = [Node::new(initial_state.clone(), None, None), Node::new(initial_state, None, None)];
+    let mut moves = Vec::new();
+    loop {
+        let player = roots[0].borrow().state.get_player_index();
+        let Some(action) = Node::best_action_with_rng(&roots[player], configs[player], rng) else { break };
+        let mut visit_distribution = Node::action_distribution(&roots[player]);
+        if visit_distribution.is_empty() {
+            visit_distribution.push((action, 1.));
+        }
+        moves.push(MoveRecord { state_hash: roots[player].borrow().state.hash_key(), action, visit_distribution });
+        roots = roots.map(|root| Node::advance(&root, action));
+    }
+    let result = roots[0].borrow().state.get_result();
+    GameRecord { moves, result }
+}
+
+// Self-play between two agents sharing a config, returning 1 if X wins, -1 if O wins and 0 for a draw
+pub fn play_self<State: GameState + Clone>(initial_state: State, config: &SearchConfig) -> i32 {
+    play_self_with_rng(initial_state, config, &mut default_rng())
+}
+
+// Like play_self(), drawing its randomness from `rng`, so a seeded game always plays out the same
+pub fn play_self_with_rng<State: GameState + Clone, R: Rng>(initial_state: State, config: &SearchConfig, rng: &mut R) -> i32 {
+    let result = play_game(initial_state, [config, config], rng);
+    if result > 0. {
+        1
+    } else if result < 0. {
+        -1
+    } else {
+        0
+    }
+}
+
+// Plays `n_games` between two configs, alternating which one moves first, with all randomness drawn from `seed`
+pub fn run_match<State: GameState + Clone>(initial_state: &State, n_games: usize, config_a: &SearchConfig, config_b: &SearchConfig, seed: u64) -> MatchResult {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut tally = MatchResult::default();
+    for game in 0..n_games {
+        let a_first = game % 2 == 0;
+        let configs = if a_first { [config_a, config_b] } else { [config_b, config_a] };
+        let result = play_game(initial_state.clone(), configs, &mut rng);
+        let result = if a_first { result } else { -result };
+        if result > 0. {
+            tally.wins += 1;
+        } else if result < 0. {
+            tally.losses += 1;
+        } else {
+            tally.draws += 1;
+        }
+    }
+    tally
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ttt;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn ttt_self_play_is_always_drawn() {
+        let config = SearchConfig::default();
+        for seed in 0..3 {
+            assert_eq!(play_self_with_rng(ttt::State::new(), &config, &mut StdRng::seed_from_u64(seed)), 0);
+        }
+        let tally = run_match(&ttt::State::new(), 6, &config, &config, 0);
+        assert_eq!(tally, MatchResult { wins: 0, losses: 0, draws: 6 });
+    }
+
+    #[test]
+    fn a_recorded_game_has_a_record_per_move() {
+        let config = SearchConfig { n_simulations: 200, ..SearchConfig::default() };
+        let record = record_game(ttt::State::new(), [&config, &config], &mut StdRng::seed_from_u64(0));
+
+        // Replaying the moves goes through the recorded positions and ends the game with the recorded result
+        let mut state = ttt::State::new();
+        for entry in &record.moves {
+            assert_eq!(entry.state_hash, state.hash_key());
+            let total: f64 = entry.visit_distribution.iter().map(|&(_, share)| share).sum();
+            assert!((total - 1.).abs() < 1e-9, "the distribution sums to {}", total);
+            assert!(entry.visit_distribution.iter().any(|&(action, _)| action == entry.action));
+            state = state.get_next_state(entry.action);
+        }
+        assert!(state.is_terminal());
+        assert_eq!(record.moves.len(), state.move_count().unwrap());
+        assert_eq!(record.result, state.get_result());
+    }
+}
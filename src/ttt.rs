@@ -1,42 +1,172 @@
-use crate::mcts::{Node, GameState};
-use std::io::stdout;
+use crate::mcts::{GameState, GameStatus};
+use crate::notation::{parse_square, square};
+use crate::player::Player;
+#[cfg(feature = "serde")]
+use crate::player::StoredPlayer;
+#[cfg(feature = "tui")]
+use crate::mcts::Node;
+#[cfg(feature = "tui")]
+use crate::ui::{self, Renderer, InputHandler};
+#[cfg(all(feature = "serde", feature = "tui"))]
+use crate::save;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "testing")]
+use std::collections::HashMap;
+#[cfg(feature = "tui")]
 use crossterm::{
-    cursor::{Show, Hide, MoveTo},
-    event::{read, Event, KeyCode},
-    execute,
-    terminal::{self, Clear, ClearType},
+    event::KeyCode,
+    style::{Color, ResetColor, SetForegroundColor},
     Result,
 };
 
 
-#[derive(Clone)]
-struct State {
+const DEFAULT_SYMBOLS: [char; 2] = ['X', 'O'];
+
+#[cfg(feature = "serde")]
+fn default_symbols() -> [char; 2] {
+    DEFAULT_SYMBOLS
+}
+
+const LINES: [[(usize, usize); 3]; 8] = [
+    [(0, 0), (0, 1), (0, 2)], [(1, 0), (1, 1), (1, 2)], [(2, 0), (2, 1), (2, 2)],
+    [(0, 0), (1, 0), (2, 0)], [(0, 1), (1, 1), (2, 1)], [(0, 2), (1, 2), (2, 2)],
+    [(0, 0), (1, 1), (2, 2)], [(0, 2), (1, 1), (2, 0)],
+];
+
+// Maps a cell to where it ends up under one of the board's rotations or reflections
+type Symmetry = fn(usize, usize) -> (usize, usize);
+
+const SYMMETRIES: [Symmetry; 8] = [
+    |i, j| (i, j), |i, j| (j, 2 - i), |i, j| (2 - i, 2 - j), |i, j| (2 - j, i),
+    |i, j| (i, 2 - j), |i, j| (2 - i, j), |i, j| (j, i), |i, j| (2 - j, 2 - i),
+];
+
+#[cfg(all(feature = "serde", feature = "tui"))]
+const SAVE_PATH: &str = "ttt_save.json";
+
+#[derive(Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(from = "SavedState"))]
+pub struct State {
+    board: Vec<Vec<char>>,
+    player: Player,
+    symbols: [char; 2],  // The first player's mark, then the second's
+    history: Option<Vec<(usize, usize)>>,
+}
+
+// A State as it's read from a save, which may be from before symbols, history or Player
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SavedState {
     board: Vec<Vec<char>>,
-    player: char,
+    player: StoredPlayer,
+    #[serde(default = "default_symbols")]
+    symbols: [char; 2],
+    #[serde(default)]
+    history: Option<Vec<(usize, usize)>>,
+}
+
+#[cfg(feature = "serde")]
+impl From<SavedState> for State {
+    fn from(saved: SavedState) -> State {
+        State { board: saved.board, player: saved.player.resolve(saved.symbols), symbols: saved.symbols, history: saved.history }
+    }
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
 }
 
 impl State {
-    fn new() -> State {
+    pub fn new() -> State {
         State {
             board: vec![vec![' '; 3]; 3],
-            player: 'X',
+            player: Player::X,
+            symbols: DEFAULT_SYMBOLS,
+            history: None,
         }
     }
+
+    // Starts recording the moves played from here on, which history() then returns. Off by default, since
+    // every state the search creates would otherwise carry its own copy of the list.
+    pub fn with_history(mut self) -> State {
+        self.history.get_or_insert_with(Vec::new);
+        self
+    }
+
+    // The moves played since with_history() was called, or None if it never was
+    pub fn history(&self) -> Option<&[(usize, usize)]> {
+        self.history.as_deref()
+    }
+
+    // Swaps in other marks for the two players, e.g. ['●', '○'], relabelling any pieces already on the board
+    pub fn with_symbols(mut self, symbols: [char; 2]) -> State {
+        assert!(symbols[0] != symbols[1] && !symbols.contains(&' '), "player symbols must be distinct and not blank");
+        let relabel = |cell: char| match cell {
+            ' ' => ' ',
+            cell if cell == self.symbols[0] => symbols[0],
+            _ => symbols[1],
+        };
+        for row in self.board.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = relabel(*cell);
+            }
+        }
+        self.symbols = symbols;
+        self
+    }
+
+    // The mark of the player whose get_player_turn() is `player`
+    pub fn symbol(&self, player: i32) -> char {
+        if player == 1 { self.symbols[0] } else { self.symbols[1] }
+    }
+
+    fn mark(&self, player: Player) -> char {
+        self.symbols[player.index()]
+    }
+
+    // The get_player_turn() of the player whose mark is on a cell
+    fn mark_value(&self, mark: char) -> i32 {
+        if mark == self.symbols[0] { 1 } else { -1 }
+    }
+
+    // The three cells of a completed line, if either player has one
+    pub fn winning_cells(&self) -> Option<Vec<(usize, usize)>> {
+        LINES.iter()
+            .find(|line| {
+                let first = self.board[line[0].0][line[0].1];
+                first != ' ' && line.iter().all(|&(i, j)| self.board[i][j] == first)
+            })
+            .map(|line| line.to_vec())
+    }
 }
 
 impl GameState for State {
     type Action = (usize, usize);
 
+    fn status(&self) -> GameStatus {
+        match self.winning_cells() {
+            Some(cells) => GameStatus::Win(self.mark_value(self.board[cells[0].0][cells[0].1])),
+            None if self.board.iter().all(|row| row.iter().all(|&cell| cell != ' ')) => GameStatus::Draw,
+            None => GameStatus::InProgress,
+        }
+    }
+
+    // The same answer as status(), but a full board needs no line scan, and nobody can have a line before
+    // X's third piece
     fn is_terminal(&self) -> bool {
-        self.get_result() != 0 || self.board.iter().all(|row| row.iter().all(|&cell| cell != ' '))
+        let pieces: usize = self.board.iter().map(|row| row.iter().filter(|&&cell| cell != ' ').count()).sum();
+        pieces == 9 || (pieces >= 5 && self.winning_cells().is_some())
     }
 
     fn get_player_turn(&self) -> i32 {
-        match self.player {
-            'X' => 1,
-            'O' => -1,
-            _ => unreachable!(),
-        }
+        self.player.value()
+    }
+
+    fn move_count(&self) -> Option<usize> {
+        Some(self.board.iter().map(|row| row.iter().filter(|&&cell| cell != ' ').count()).sum())
     }
 
     fn get_legal_actions(&self) -> Vec<Self::Action> {
@@ -53,114 +183,247 @@ impl GameState for State {
         actions
     }
 
-    fn get_policy(&self, actions: &Vec<Self::Action>) -> Vec<f64> {
+    fn get_policy(&self, actions: &[Self::Action]) -> Vec<f64> {
         vec![1.0 / actions.len() as f64; actions.len()]
     }
 
     fn get_next_state(&self, action: Self::Action) -> State {
         let mut next_board = self.board.clone();
-        next_board[action.0][action.1] = self.player;
-        let next_player = if self.player == 'X' { 'O' } else { 'X' };
-
+        next_board[action.0][action.1] = self.mark(self.player);
         State {
             board: next_board,
-            player: next_player,
+            player: self.player.opponent(),
+            symbols: self.symbols,
+            history: self.history.as_ref().map(|history| [history.as_slice(), &[action]].concat()),
         }
     }
 
-    fn get_result(&self) -> i32 {
-        for i in 0..3 {
-            if self.board[i][0] == self.board[i][1] && self.board[i][1] == self.board[i][2] && self.board[i][0] != ' ' {
-                return -1;
-            }
-            if self.board[0][i] == self.board[1][i] && self.board[1][i] == self.board[2][i] && self.board[0][i] != ' ' {
-                return -1;
-            }
-        }
-        if (self.board[0][0] == self.board[1][1] && self.board[1][1] == self.board[2][2] && self.board[0][0] != ' ')
-            || (self.board[0][2] == self.board[1][1] && self.board[1][1] == self.board[2][0] && self.board[0][2] != ' ') {
-            return -1;
-        }
+    // Columns a-c from the left, rows 1-3 from the bottom, so (0, 0) in the top left corner is "a3"
+    fn action_to_string(&self, &(row, col): &Self::Action) -> String {
+        square(col, 2 - row)
+    }
 
-        0
+    fn parse_action(&self, text: &str) -> Option<Self::Action> {
+        parse_square(text).filter(|&(col, row)| col < 3 && row < 3).map(|(col, row)| (2 - row, col))
+    }
+
+    fn hash_key(&self) -> u64 {
+        // The history shouldn't split transpositions
+        let mut hasher = DefaultHasher::new();
+        self.board.hash(&mut hasher);
+        self.player.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // The smallest hash over the 8 symmetric boards
+    fn canonical_key(&self) -> u64 {
+        SYMMETRIES.iter()
+            .map(|transform| {
+                let mut board = vec![vec![' '; 3]; 3];
+                for (i, row) in self.board.iter().enumerate() {
+                    for (j, &cell) in row.iter().enumerate() {
+                        let (ti, tj) = transform(i, j);
+                        board[ti][tj] = cell;
+                    }
+                }
+                let mut hasher = DefaultHasher::new();
+                board.hash(&mut hasher);
+                self.player.hash(&mut hasher);
+                hasher.finish()
+            })
+            .min()
+            .unwrap()
     }
 }
 
 
-pub fn play() -> Result<()> {
-    // Terminal setup
-    let mut stdout = stdout();
-    terminal::enable_raw_mode()?;
-    execute!(stdout, Hide, Clear(ClearType::All))?;
+// The result with perfect play from `state` on, as get_result() would give it at the end: 1 if the first player
+// wins, -1 if the second does and 0 for a draw. Searches the whole game tree, so it's meant for checking the MCTS
+// rather than for playing.
+#[cfg(feature = "testing")]
+pub fn solve(state: &State) -> i32 {
+    // Symmetric positions have the same value, so they share a cache entry
+    fn minimax(state: &State, cache: &mut HashMap<u64, i32>) -> i32 {
+        if state.is_terminal() {
+            return state.get_result() as i32;
+        }
+        if let Some(&value) = cache.get(&state.canonical_key()) {
+            return value;
+        }
+        let values = state.get_legal_actions().into_iter().map(|action| minimax(&state.get_next_state(action), cache));
+        let value = if state.get_player_turn() == 1 { values.max() } else { values.min() }.unwrap();
+        cache.insert(state.canonical_key(), value);
+        value
+    }
+    minimax(state, &mut HashMap::new())
+}
 
-    // Game state initialization
-    let mut root = Node::new(State::new(), None, None);
-    let mut current_pos: <State as GameState>::Action = (0, 0);
 
-    // Main game loop
-    loop {
-        execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+#[cfg(feature = "tui")]
+struct BoardRenderer;
+
+#[cfg(feature = "tui")]
+impl Renderer<State> for BoardRenderer {
+    fn render(&self, state: &State, cursor: (usize, usize), hint: Option<(usize, usize)>) {
+        let winning_cells = state.winning_cells().unwrap_or_default();
         println!("+---+---+---+\r");
         for i in 0..3 {
             for j in 0..3 {
-                let cell = root.borrow().state.board[i][j];
-                let marker = if (i, j) == current_pos { format!("[{}]", cell) } else { format!(" {} ", cell) };
+                let mut cell = state.board[i][j].to_string();
+                if winning_cells.contains(&(i, j)) {
+                    cell = format!("{}{}{}", SetForegroundColor(Color::Green), cell, ResetColor);
+                } else if hint == Some((i, j)) {
+                    cell = format!("{}*{}", SetForegroundColor(Color::Yellow), ResetColor);
+                }
+                let marker = if (i, j) == cursor { format!("[{}]", cell) } else { format!(" {} ", cell) };
                 print!("|{}", marker);
             }
             println!("|\r");
             println!("+---+---+---+\r")
         }
+    }
+
+    fn player_symbol(&self, state: &State, player: i32) -> char {
+        state.symbol(player)
+    }
+}
+
+#[cfg(feature = "tui")]
+struct KeyHandler;
+
+#[cfg(feature = "tui")]
+impl InputHandler<State> for KeyHandler {
+    fn initial_cursor(&self) -> (usize, usize) {
+        (0, 0)
+    }
+
+    fn move_cursor(&self, (row, col): (usize, usize), key: KeyCode) -> Option<(usize, usize)> {
+        match key {
+            KeyCode::Up if row > 0 => Some((row - 1, col)),
+            KeyCode::Down if row < 2 => Some((row + 1, col)),
+            KeyCode::Left if col > 0 => Some((row, col - 1)),
+            KeyCode::Right if col < 2 => Some((row, col + 1)),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn save(&self, state: &State) -> Result<()> {
+        save::save_game(state, SAVE_PATH)
+    }
+}
+
+
+#[cfg(feature = "tui")]
+pub fn play(n_simulations: Option<i32>, options: ui::Options, symbols: Option<[char; 2]>, load_path: Option<&str>) -> Result<()> {
+    let mut initial_state = match load_path {
+        #[cfg(feature = "serde")]
+        Some(path) => save::load_game(path)?,
+        _ => State::new(),
+    };
+    if let Some(symbols) = symbols {
+        initial_state = initial_state.with_symbols(symbols);
+    }
+    let options = ui::Options { simulations: n_simulations, ..options };
+    ui::run_game(initial_state, options, &BoardRenderer, &KeyHandler, |root, simulations| Node::best_action(root, simulations.unwrap_or(1000)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    fn after(moves: &[(usize, usize)]) -> State {
+        moves.iter().fold(State::new(), |state, &action| state.get_next_state(action))
+    }
 
-        if root.borrow().state.is_terminal() {
-            break;
+    #[test]
+    fn get_result_reports_the_winner() {
+        let x_wins = after(&[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]);
+        assert_eq!(x_wins.status(), GameStatus::Win(1));
+        assert_eq!(x_wins.get_result(), 1.);
+
+        let o_wins = after(&[(0, 0), (1, 0), (0, 1), (1, 1), (2, 2), (1, 2)]);
+        assert_eq!(o_wins.status(), GameStatus::Win(-1));
+        assert_eq!(o_wins.get_result(), -1.);
+
+        let draw = after(&[(0, 0), (1, 1), (2, 2), (0, 2), (2, 0), (1, 0), (1, 2), (2, 1), (0, 1)]);
+        assert_eq!(draw.status(), GameStatus::Draw);
+        assert_eq!(draw.get_result(), 0.);
+
+        assert_eq!(State::new().get_result(), 0.);
+    }
+
+    #[test]
+    fn a_full_board_is_a_draw_rather_than_in_progress() {
+        let moves = [(0, 0), (1, 1), (2, 2), (0, 2), (2, 0), (1, 0), (1, 2), (2, 1)];
+        let one_left = after(&moves);
+        assert_eq!(one_left.status(), GameStatus::InProgress);
+        assert!(!one_left.is_terminal());
+        let full = one_left.get_next_state((0, 1));
+        assert_eq!(full.status(), GameStatus::Draw);
+        assert!(full.is_terminal());
+        // Both score 0, so only status() tells them apart
+        assert_eq!(one_left.get_result(), full.get_result());
+    }
+
+    #[test]
+    fn symmetric_boards_share_a_canonical_key() {
+        // X in a corner and O beside it, in each of the eight orientations
+        let orientations = [
+            [(0, 0), (0, 1)], [(0, 2), (1, 2)], [(2, 2), (2, 1)], [(2, 0), (1, 0)],
+            [(0, 2), (0, 1)], [(2, 0), (2, 1)], [(0, 0), (1, 0)], [(2, 2), (1, 2)],
+        ];
+        let key = after(&orientations[0]).canonical_key();
+        for moves in orientations {
+            assert_eq!(after(&moves).canonical_key(), key);
         }
+        // O on the far side of the corner isn't the same position
+        assert_ne!(after(&[(0, 0), (2, 2)]).canonical_key(), key);
+        assert_ne!(after(&orientations[0]).hash_key(), after(&orientations[1]).hash_key());
+    }
 
-        if root.borrow().state.player == 'X' {
-            // Human turn
-            if let Event::Key(key_event) = read()? {
-                match key_event.code {
-                    KeyCode::Up => if current_pos.0 > 0 { current_pos.0 -= 1; },
-                    KeyCode::Down => if current_pos.0 < 2 { current_pos.0 += 1; },
-                    KeyCode::Left => if current_pos.1 > 0 { current_pos.1 -= 1; },
-                    KeyCode::Right => if current_pos.1 < 2 { current_pos.1 += 1; },
-                    KeyCode::Enter | KeyCode::Char(' ') => {
-                        if root.borrow().state.get_legal_actions().contains(&current_pos) {
-                            let next_state = root.borrow().state.get_next_state(current_pos);
-                            root = Node::new(next_state, None, Some(current_pos));
-                        }
-                    },
-                    KeyCode::Esc => break,
-                    _ => {}
+    #[test]
+    fn is_terminal_agrees_with_status() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..300 {
+            let mut state = State::new();
+            loop {
+                assert_eq!(state.is_terminal(), state.status() != GameStatus::InProgress);
+                if state.is_terminal() {
+                    break;
                 }
+                state = state.get_next_state(*state.get_legal_actions().choose(&mut rng).unwrap());
             }
-        } else {
-            // AI turn
-            let action = Node::best_action(&root, 1000);
-            let next_state = root.borrow().state.get_next_state(action);
-            root = Node::new(next_state, None, Some(action));
         }
     }
 
-    let state = &root.borrow().state;
-    let result_message = match state.get_result() {
-        -1 => match state.player {
-            'O' => "Player X wins!",
-            'X' => "Player O wins!",
-            _ => unreachable!(),
-        },
-        0 => "It's a draw!",
-        _ => unreachable!(),
-    };
+    #[cfg(feature = "testing")]
+    #[test]
+    fn solve_scores_perfect_play() {
+        assert_eq!(solve(&State::new()), 0);
+        // X to move with two in the top row
+        assert_eq!(solve(&after(&[(0, 0), (1, 1), (0, 1), (2, 2)])), 1);
+        // O to move, with X threatening (1, 0) and (0, 2)
+        assert_eq!(solve(&after(&[(0, 0), (0, 1), (1, 1), (2, 2), (2, 0)])), 1);
+        // O to move, able to win on the spot
+        assert_eq!(solve(&after(&[(0, 0), (1, 1), (2, 2), (0, 2), (2, 1)])), -1);
+    }
 
-    if state.is_terminal() {
-        execute!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
-        println!("{}", result_message);
-        loop {
-            if let Event::Key(_) = read()? { break; }
+    #[cfg(feature = "testing")]
+    #[test]
+    fn the_search_keeps_the_wins_that_solve_finds() {
+        use crate::mcts::Node;
+        // X to move with a win on the spot, and with a fork at (2, 0) for a win two moves later
+        let won = [after(&[(0, 0), (1, 1), (0, 1), (2, 2)]), after(&[(0, 0), (0, 1), (1, 1), (2, 2)])];
+        for state in won {
+            let expected = solve(&state);
+            assert_eq!(expected, 1);
+            let action = Node::best_action_seeded(&Node::new(state.clone(), None, None), 1000, 0).unwrap();
+            assert_eq!(solve(&state.get_next_state(action)), expected, "{:?} throws the win away", action);
         }
     }
-
-    terminal::disable_raw_mode()?;
-    execute!(stdout, Show, MoveTo(0, 0), Clear(ClearType::All))?;
-    Ok(())
 }
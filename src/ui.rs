@@ -0,0 +1,337 @@
+use crate::mcts::{Node, GameState, GameStatus, SearchConfig, ChildStat};
+use std::io::stdout;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::time::Duration;
+use crossterm::{
+    cursor::{Show, Hide, MoveTo},
+    event::{poll, read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEvent, MouseEventKind},
+    execute,
+    style::{Color, ResetColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
+    Result,
+};
+
+
+// Pondering stops once the root has this many visits, to bound memory while the human is away
+const MAX_PONDER_VISITS: i32 = 200_000;
+const HINT_SIMULATIONS: i32 = 1000;
+const REVIEW_SIMULATIONS: i32 = 2000;
+// The simulation counts + and - step through, and how a game's own default counts when stepping from it
+const SIMULATION_STEPS: [i32; 10] = [100, 200, 500, 1000, 2000, 5000, 10_000, 20_000, 50_000, 100_000];
+const DEFAULT_STEP: i32 = 1000;
+
+// How run_game() is played, beyond the game itself
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    // The get_player_turn() of the human, or None to have the AI play both sides, each move waiting for Enter
+    pub human: Option<i32>,
+    // Keep growing the tree while the human thinks, so the AI can reuse whatever is below the move they pick
+    pub ponder: bool,
+    // Print the visits and mean value of each of the AI's candidate moves below the board after it moves
+    pub verbose: bool,
+    // Simulations per AI move, which + and - change during the game, or None for the game's own default
+    pub simulations: Option<i32>,
+}
+
+pub trait Renderer<S: GameState> {
+    // Prints the board with the cursor marked, which is also redrawn above the result once the game ends.
+    // `hint` is the move suggested after the human presses 'h', to be highlighted until the next key.
+    // The terminal is in raw mode, so lines need to end in \r
+    fn render(&self, state: &S, cursor: S::Action, hint: Option<S::Action>);
+
+    // The mark shown for the player whose get_player_turn() is `player`, used in the result message
+    fn player_symbol(&self, _state: &S, player: i32) -> char {
+        if player == 1 { 'X' } else { 'O' }
+    }
+
+    // How a move is named in the verbose stats table
+    fn action_label(&self, action: S::Action) -> String {
+        format!("{:?}", action)
+    }
+}
+
+pub trait InputHandler<S: GameState> {
+    fn initial_cursor(&self) -> S::Action;
+    // Where `key` moves the cursor to, or None if it isn't a cursor key or the cursor is already at the edge
+    fn move_cursor(&self, cursor: S::Action, key: KeyCode) -> Option<S::Action>;
+
+    // The cursor to use for the human's move in `state`, e.g. kept within a pile that has shrunk, or on a forced
+    // pass. Called before the board is drawn on each of the human's turns.
+    fn fit_cursor(&self, _state: &S, cursor: S::Action) -> S::Action {
+        cursor
+    }
+
+    // The action under a left click at the given terminal column and row, if any
+    fn click(&self, _column: u16, _row: u16) -> Option<S::Action> {
+        None
+    }
+
+    // Called when the human presses 's'
+    #[cfg(feature = "serde")]
+    fn save(&self, _state: &S) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Runs an interactive game where the human plays the cursor's action with Enter, Space or a click, takes back
+// a move with 'u' or Backspace, asks for a hint with 'h' and quits with Esc, while `ai` picks the moves for the
+// other player. See Options for watching the AI play itself, pondering and the verbose stats. '+' and '-' make the
+// AI stronger or weaker for its following moves by passing `ai` more or fewer simulations. Once the game is over,
+// 'r' steps back through it with the engine's view of each move (see review()).
+pub fn run_game<S, R, I, F>(initial_state: S, options: Options, renderer: &R, input_handler: &I, mut ai: F) -> Result<()>
+where
+    S: GameState + Clone,
+    R: Renderer<S>,
+    I: InputHandler<S>,
+    F: FnMut(&Rc<RefCell<Node<S>>>, Option<i32>) -> Option<S::Action>,
+{
+    // Terminal setup
+    let mut stdout = stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, Hide, EnableMouseCapture, Clear(ClearType::All))?;
+
+    // Game state initialization
+    let mut root = Node::new(initial_state, None, None);
+    let mut moves: Vec<(S, S::Action)> = Vec::new();  // Each move played and the state before it, for the review
+    let mut history: Vec<usize> = Vec::new();  // Where each human move is in `moves`, for takebacks
+    let mut current_pos = input_handler.initial_cursor();
+    let ponder_config = SearchConfig { n_simulations: 100, ..SearchConfig::default() };
+    let mut hint: Option<Option<S::Action>> = None;  // Set while a hint is shown, to None inside if there was no move
+    let mut last_stats: Option<Vec<ChildStat<S::Action>>> = None;  // From the AI's last search, for verbose output
+    let mut last_verdict: Option<String> = None;  // The AI's chances after that search, also for verbose output
+    let Options { human, ponder, verbose, mut simulations } = options;
+
+    // Main game loop
+    loop {
+        if human == Some(root.borrow().state.get_player_turn()) {
+            current_pos = input_handler.fit_cursor(&root.borrow().state, current_pos);
+        }
+        execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+        renderer.render(&root.borrow().state, current_pos, hint.flatten());
+        if hint == Some(None) {
+            println!("No moves to suggest\r");
+        }
+        if let Some(stats) = last_stats.as_ref().filter(|_| verbose) {
+            print_stats(renderer, stats);
+        }
+        if let Some(verdict) = last_verdict.as_ref().filter(|_| verbose) {
+            println!("{}\r", verdict);
+        }
+        if !root.borrow().state.is_terminal() {
+            match simulations {
+                Some(simulations) => println!("AI: {} simulations a move, +/- to change\r", simulations),
+                None => println!("AI: default strength, +/- to change\r"),
+            }
+        }
+
+        if root.borrow().state.is_terminal() {
+            break;
+        }
+
+        if human == Some(root.borrow().state.get_player_turn()) {
+            // Human turn, searching in bursts between checks for input when pondering
+            if ponder {
+                while !poll(Duration::ZERO)? && root.borrow().visits() < MAX_PONDER_VISITS {
+                    Node::ponder(&root, &ponder_config);
+                }
+            }
+            let event = read()?;
+            hint = None;
+            let play = match event {
+                Event::Key(key_event) => {
+                    if let Some(pos) = input_handler.move_cursor(current_pos, key_event.code) {
+                        current_pos = pos;
+                    }
+                    match key_event.code {
+                        KeyCode::Enter | KeyCode::Char(' ') => true,
+                        KeyCode::Char('u') | KeyCode::Backspace => {
+                            // Take back the last human move and the AI's reply
+                            if let Some(index) = history.pop() {
+                                root = Node::new(moves[index].0.clone(), None, None);
+                                moves.truncate(index);
+                                last_stats = None;
+                                last_verdict = None;
+                            }
+                            false
+                        },
+                        KeyCode::Char('h') => {
+                            // Search a throwaway root so the game's own tree is left alone
+                            let hint_root = Node::new(root.borrow().state.clone(), None, None);
+                            hint = Some(Node::best_action(&hint_root, HINT_SIMULATIONS));
+                            false
+                        },
+                        #[cfg(feature = "serde")]
+                        KeyCode::Char('s') => {
+                            input_handler.save(&root.borrow().state)?;
+                            false
+                        },
+                        KeyCode::Char('+') | KeyCode::Char('=') => {
+                            simulations = Some(step_simulations(simulations, true));
+                            false
+                        },
+                        KeyCode::Char('-') => {
+                            simulations = Some(step_simulations(simulations, false));
+                            false
+                        },
+                        KeyCode::Esc => break,
+                        _ => false,
+                    }
+                },
+                Event::Mouse(MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row, .. }) => {
+                    match input_handler.click(column, row) {
+                        Some(pos) => {
+                            current_pos = pos;
+                            true
+                        },
+                        None => false,
+                    }
+                },
+                _ => false,
+            };
+
+            // Moves that aren't legal, like clicks on a full column, are ignored
+            if play && root.borrow().state.get_legal_actions().contains(&current_pos) {
+                history.push(moves.len());
+                moves.push((root.borrow().state.clone(), current_pos));
+                root = Node::advance(&root, current_pos);
+            }
+        } else {
+            // AI turn
+            if human.is_none() {
+                println!("Press Enter for the next move, or Esc to quit\r");
+                match read()? {
+                    Event::Key(key_event) if key_event.code == KeyCode::Enter => {},
+                    Event::Key(key_event) if key_event.code == KeyCode::Esc => break,
+                    Event::Key(key_event) if matches!(key_event.code, KeyCode::Char('+' | '=' | '-')) => {
+                        simulations = Some(step_simulations(simulations, key_event.code != KeyCode::Char('-')));
+                        continue;
+                    },
+                    _ => continue,
+                }
+            }
+            match ai(&root, simulations) {
+                Some(action) => {
+                    if verbose {
+                        let state = &root.borrow().state;
+                        let symbol = renderer.player_symbol(state, state.get_player_turn());
+                        last_stats = Some(Node::child_stats(&root));
+                        last_verdict = Some(match Node::moves_to_win(&root) {
+                            Some(moves) => format!("{} has a forced win in {} move{}", symbol, moves, if moves == 1 { "" } else { "s" }),
+                            None => format!("{}'s winning chances: {:.0}%", symbol, 100. * Node::win_probability(&root)),
+                        });
+                    }
+                    moves.push((root.borrow().state.clone(), action));
+                    root = Node::advance(&root, action);
+                },
+                None => break,
+            }
+        }
+    }
+
+    let state = &root.borrow().state;
+    let result_message = match state.status() {
+        GameStatus::Win(player) => format!("Player {} wins!", renderer.player_symbol(state, player)),
+        GameStatus::Draw | GameStatus::InProgress => "It's a draw!".to_string(),
+    };
+
+    if state.is_terminal() {
+        execute!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+        renderer.render(state, current_pos, None);
+        println!("{}\r", result_message);
+        if !moves.is_empty() {
+            println!("Press r to review the game, or any other key to quit\r");
+        }
+        loop {
+            if let Event::Key(key_event) = read()? {
+                if key_event.code == KeyCode::Char('r') && !moves.is_empty() {
+                    review(renderer, &moves, state, &result_message)?;
+                }
+                break;
+            }
+        }
+    }
+
+    terminal::disable_raw_mode()?;
+    execute!(stdout, DisableMouseCapture, Show, MoveTo(0, 0), Clear(ClearType::All))?;
+    Ok(())
+}
+
+// The next of SIMULATION_STEPS above or below `simulations`, staying at the ends
+fn step_simulations(simulations: Option<i32>, up: bool) -> i32 {
+    let current = simulations.unwrap_or(DEFAULT_STEP);
+    let next = if up {
+        SIMULATION_STEPS.iter().find(|&&step| step > current)
+    } else {
+        SIMULATION_STEPS.iter().rev().find(|&&step| step < current)
+    };
+    next.copied().unwrap_or(if up { SIMULATION_STEPS[SIMULATION_STEPS.len() - 1] } else { SIMULATION_STEPS[0] })
+}
+
+// The engine's choice for a position under review, and its candidate moves
+type Analysis<Action> = (Option<Action>, Vec<ChildStat<Action>>);
+
+// Steps through a finished game with Left and Right, or Home and End, until Esc. Each position is drawn with the
+// move played under the cursor and the engine's choice from a fresh search marked as a hint, along with the values
+// of both for the player who moved and the engine's candidate moves. Moves the engine wouldn't have played are
+// flagged in yellow. Positions are searched the first time they're shown.
+fn review<S, R>(renderer: &R, moves: &[(S, S::Action)], final_state: &S, result_message: &str) -> Result<()>
+where
+    S: GameState + Clone,
+    R: Renderer<S>,
+{
+    let mut analyses: Vec<Option<Analysis<S::Action>>> = moves.iter().map(|_| None).collect();
+    let mut index = 0;
+    loop {
+        execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0))?;
+        match moves.get(index) {
+            Some((state, played)) => {
+                let (best, stats) = analyses[index].get_or_insert_with(|| {
+                    let root = Node::new(state.clone(), None, None);
+                    let best = Node::best_action(&root, REVIEW_SIMULATIONS);
+                    (best, Node::child_stats(&root))
+                });
+                let value = |action: S::Action| stats.iter().find(|stat| stat.action == action)
+                    .map_or(String::new(), |stat| format!(" (value {:.3})", stat.mean_value));
+                renderer.render(state, *played, *best);
+                let mover = renderer.player_symbol(state, state.get_player_turn());
+                println!("Move {} of {}: {} played {}{}\r", index + 1, moves.len(), mover, renderer.action_label(*played), value(*played));
+                match *best {
+                    Some(best) if best == *played => println!("The engine agrees\r"),
+                    Some(best) => println!("{}The engine prefers {}{}, marked *{}\r",
+                        SetForegroundColor(Color::Yellow), renderer.action_label(best), value(best), ResetColor),
+                    None => println!("The engine has no move to suggest\r"),
+                }
+                print_stats(renderer, stats);
+            },
+            None => {
+                renderer.render(final_state, moves[moves.len() - 1].1, None);
+                println!("Final position: {}\r", result_message);
+            },
+        }
+        println!("\r");
+        println!("Left and Right step through the game, Esc leaves the review\r");
+
+        if let Event::Key(key_event) = read()? {
+            match key_event.code {
+                KeyCode::Left => index = index.saturating_sub(1),
+                KeyCode::Right => index = (index + 1).min(moves.len()),
+                KeyCode::Home => index = 0,
+                KeyCode::End => index = moves.len(),
+                KeyCode::Esc => return Ok(()),
+                _ => {},
+            }
+        }
+    }
+}
+
+// A table of the AI's candidate moves, most visited first
+fn print_stats<S: GameState, R: Renderer<S>>(renderer: &R, stats: &[ChildStat<S::Action>]) {
+    let mut stats: Vec<&ChildStat<S::Action>> = stats.iter().collect();
+    stats.sort_by_key(|stat| -stat.visit_count);
+    println!("\r");
+    println!("{:>8} {:>8} {:>8}\r", "Move", "Visits", "Value");
+    for stat in stats {
+        println!("{:>8} {:>8} {:>8.3}\r", renderer.action_label(stat.action), stat.visit_count, stat.mean_value);
+    }
+}